@@ -0,0 +1,1676 @@
+use std::{
+    any::Any,
+    borrow::Cow,
+    collections::HashMap,
+    future::Future,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    panic::{self, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{OwnedSemaphorePermit, RwLock, Semaphore, mpsc, oneshot};
+
+use crate::{
+    config::{AdaptiveWindow, BatchConfig, BatchConfigCell, Rate},
+    error::Error,
+    metrics,
+    retry::Retry,
+    stats::{BatchStats, Stats},
+    tracing::{Instrument, Span, TracingSpan, info_span},
+};
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+type BatchFn<In, Out> = Arc<dyn Fn(Vec<In>) -> BoxFuture<Out> + Send + Sync>;
+type SpanFn = Arc<dyn Fn(usize) -> Span + Send + Sync>;
+type SplitFn<Out> = fn(&mut Out, usize) -> Out;
+type ResultsFn<Out> = fn(&Out) -> Option<usize>;
+type WeightFn<In> = Arc<dyn Fn(&In) -> u64 + Send + Sync>;
+
+/// How the result of a batch is handed out to the callers of the batch
+pub trait Fanout<Out>: 'static {
+    /// Result of a call with a single item
+    type Single;
+
+    /// Appends the result of the next chunk of a call whose items were spread over several
+    /// batches. `None` if results can't be combined, such calls always run in a single batch
+    const MERGE: Option<fn(&mut Out, Out)>;
+
+    /// Takes the result for a caller that submitted `count` items
+    fn split(result: &mut Out, count: usize) -> Out;
+    fn single(result: Out) -> Self::Single;
+    /// Amount of results in a batch result, `None` if it isn't split by item
+    fn results(result: &Out) -> Option<usize>;
+}
+
+/// Every caller receives a clone of the batch result
+pub struct Cloned;
+
+impl<Out: Clone + 'static> Fanout<Out> for Cloned {
+    type Single = Out;
+    const MERGE: Option<fn(&mut Out, Out)> = None;
+
+    fn split(result: &mut Out, _count: usize) -> Out {
+        result.clone()
+    }
+
+    fn single(result: Out) -> Self::Single {
+        result
+    }
+
+    fn results(_result: &Out) -> Option<usize> {
+        None
+    }
+}
+
+/// Every caller receives the results of its own items, in the same order as the input
+pub struct Split;
+
+impl<Out: SplitOutput + 'static> Fanout<Out> for Split {
+    type Single = Out::Item;
+    const MERGE: Option<fn(&mut Out, Out)> = Some(Out::append);
+
+    fn split(result: &mut Out, count: usize) -> Out {
+        result.split_off_front(count)
+    }
+
+    fn single(result: Out) -> Self::Single {
+        result.into_single()
+    }
+
+    fn results(result: &Out) -> Option<usize> {
+        result.results()
+    }
+}
+
+/// Fanouts whose batch result can be expanded from the unique items of a batch back to every
+/// item, see [`BatcherBuilder::dedupe`]
+pub trait Dedupe<Out>: Fanout<Out> {
+    /// Result of the full batch, where `indices` maps every item to its unique item
+    fn expand(result: Out, indices: &[usize]) -> Out;
+}
+
+impl<Out: Clone + 'static> Dedupe<Out> for Cloned {
+    fn expand(result: Out, _indices: &[usize]) -> Out {
+        result
+    }
+}
+
+impl<T: Clone + 'static> Dedupe<Vec<T>> for Split {
+    fn expand(result: Vec<T>, indices: &[usize]) -> Vec<T> {
+        // Too few results are left as is, callers then fail with `Error::MissingResults`
+        if indices.iter().any(|index| *index >= result.len()) {
+            return result;
+        }
+
+        indices.iter().map(|index| result[*index].clone()).collect()
+    }
+}
+
+impl<T: Clone + 'static, E: Clone + 'static> Dedupe<Result<Vec<T>, E>> for Split {
+    fn expand(result: Result<Vec<T>, E>, indices: &[usize]) -> Result<Vec<T>, E> {
+        result.map(|result| Self::expand(result, indices))
+    }
+}
+
+/// Batch result holding one value per input item
+pub trait SplitOutput: Sized {
+    type Item;
+
+    /// Removes the results of the first `count` items
+    fn split_off_front(&mut self, count: usize) -> Self;
+    /// Adds the results of the items following the current ones
+    fn append(&mut self, other: Self);
+    /// Result of a batch with a single item
+    fn into_single(self) -> Self::Item;
+    /// Amount of results, `None` if there are none to split (e.g. an error shared by every item)
+    fn results(&self) -> Option<usize>;
+}
+
+impl<T> SplitOutput for Vec<T> {
+    type Item = T;
+
+    fn split_off_front(&mut self, count: usize) -> Self {
+        self.drain(..count).collect()
+    }
+
+    fn append(&mut self, mut other: Self) {
+        Vec::append(self, &mut other);
+    }
+
+    fn into_single(self) -> Self::Item {
+        self.into_iter()
+            .next()
+            .expect("batched function returned less results than items")
+    }
+
+    fn results(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T, E: Clone> SplitOutput for Result<Vec<T>, E> {
+    type Item = Result<T, E>;
+
+    fn split_off_front(&mut self, count: usize) -> Self {
+        match self {
+            Ok(result) => Ok(result.split_off_front(count)),
+            Err(error) => Err(error.clone()),
+        }
+    }
+
+    fn append(&mut self, other: Self) {
+        match (&mut *self, other) {
+            (Ok(result), Ok(other)) => result.extend(other),
+            (Ok(_), Err(error)) => *self = Err(error),
+            (Err(_), _) => {}
+        }
+    }
+
+    fn into_single(self) -> Self::Item {
+        self.map(SplitOutput::into_single)
+    }
+
+    fn results(&self) -> Option<usize> {
+        self.as_ref().ok().map(Vec::len)
+    }
+}
+
+struct Message<In, Out> {
+    items: Vec<In>,
+    /// Cumulative weight of the items
+    weight: u64,
+    priority: Priority,
+    span: Span,
+    response: Option<Response<Out>>,
+}
+
+/// Where (and how) the result of a caller is sent, absent for fire and forget calls
+struct Response<Out> {
+    sender: oneshot::Sender<Result<Out, Error>>,
+    split: SplitFn<Out>,
+    results: ResultsFn<Out>,
+}
+
+/// Responses of a running batch. Callers still waiting when it's dropped (because the task was
+/// cancelled) receive [`Error::Cancelled`]
+struct PendingResponses<Out> {
+    responses: Vec<(Option<Response<Out>>, usize)>,
+}
+
+impl<Out> PendingResponses<Out> {
+    /// Hands out the result, or fails every caller with [`Error::MissingResults`] if it has
+    /// fewer results than the callers submitted items
+    fn send(mut self, mut result: Out) {
+        let mut responses = self.responses.iter().flat_map(|(response, _)| response);
+        let results = responses.next().map(|response| response.results);
+        if let Some(returned) = results.and_then(|results| results(&result)) {
+            let expected = self.items();
+            if returned < expected {
+                return self.fail(Error::MissingResults { expected, returned });
+            }
+        }
+
+        for (response, count) in self.responses.drain(..) {
+            if let Some(response) = response {
+                let _ = response
+                    .sender
+                    .send(Ok((response.split)(&mut result, count)));
+            }
+        }
+    }
+
+    /// Amount of items the callers submitted
+    fn items(&self) -> usize {
+        self.responses.iter().map(|(_, count)| count).sum()
+    }
+
+    /// Moves the callers from `at` onwards into a new set of responses
+    fn split_off(&mut self, at: usize) -> Self {
+        Self {
+            responses: self.responses.split_off(at),
+        }
+    }
+
+    fn fail(mut self, error: Error) {
+        for (response, _) in self.responses.drain(..) {
+            if let Some(response) = response {
+                let _ = response.sender.send(Err(error.clone()));
+            }
+        }
+    }
+}
+
+impl<Out> Drop for PendingResponses<Out> {
+    fn drop(&mut self) {
+        for (response, _) in self.responses.drain(..) {
+            if let Some(response) = response {
+                let _ = response.sender.send(Err(Error::Cancelled));
+            }
+        }
+    }
+}
+
+/// Batch future that completes with the panic payload instead of unwinding if the batch function
+/// panics, so the collector (and the other batches) keep running
+struct CatchUnwind<T> {
+    future: BoxFuture<T>,
+}
+
+impl<T> Future for CatchUnwind<T> {
+    type Output = Result<T, Box<dyn Any + Send>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.future.as_mut().poll(cx))) {
+            Ok(Poll::Ready(result)) => Poll::Ready(Ok(result)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => Poll::Ready(Err(payload)),
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+struct Batch<In, Out> {
+    items: Vec<In>,
+    weight: u64,
+    spans: Vec<Span>,
+    responses: Vec<(Option<Response<Out>>, usize)>,
+}
+
+impl<In, Out> Batch<In, Out> {
+    fn new() -> Self {
+        Self {
+            items: vec![],
+            weight: 0,
+            spans: vec![],
+            responses: vec![],
+        }
+    }
+
+    /// Whether no further call can join the batch. An empty batch is never full, so a limit (or
+    /// maximum weight) of 0 still lets every call run in a batch of its own
+    fn is_full(&self, config: &BatchConfig) -> bool {
+        !self.responses.is_empty()
+            && (self.items.len() >= config.limit() || self.weight >= config.max_weight())
+    }
+
+    /// Whether a call can join the batch without exceeding the limit and maximum weight. A call
+    /// too large for any batch runs in a batch of its own
+    fn fits(&self, message: &Message<In, Out>, config: &BatchConfig) -> bool {
+        self.responses.is_empty()
+            || (self.items.len() + message.items.len() <= config.limit()
+                && self.weight.saturating_add(message.weight) <= config.max_weight())
+    }
+
+    fn push(&mut self, mut message: Message<In, Out>) {
+        self.weight = self.weight.saturating_add(message.weight);
+        self.responses.push((message.response, message.items.len()));
+        self.spans.push(message.span);
+        self.items.append(&mut message.items);
+    }
+
+    /// Removes the items of callers that stopped waiting for their result
+    fn withdraw_cancelled(&mut self) {
+        let mut items = std::mem::take(&mut self.items).into_iter();
+        let spans = std::mem::take(&mut self.spans);
+        let responses = std::mem::take(&mut self.responses);
+
+        for ((response, count), span) in responses.into_iter().zip(spans) {
+            let call_items = items.by_ref().take(count);
+            if response
+                .as_ref()
+                .is_some_and(|response| response.sender.is_closed())
+            {
+                call_items.for_each(drop);
+                continue;
+            }
+
+            self.items.extend(call_items);
+            self.spans.push(span);
+            self.responses.push((response, count));
+        }
+    }
+}
+
+/// Why a batch stopped collecting calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlushReason {
+    /// The batch reached the size limit
+    Limit,
+    /// The window of the batch elapsed
+    Window,
+    /// The batcher was flushed
+    Flush,
+    /// The batcher was shut down (or dropped)
+    Shutdown,
+    /// An urgent call joined the batch
+    Urgent,
+}
+
+/// How soon a call runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Priority {
+    /// The call joins the next batch and waits for its window
+    #[default]
+    Normal,
+    /// The call joins the next batch, which starts right away. Useful for interactive requests
+    /// while background jobs keep filling large batches
+    Urgent,
+}
+
+/// Messages received by the collector task
+enum Command<In, Out> {
+    Call(Message<In, Out>),
+    /// Processes the pending batch right away
+    Flush(oneshot::Sender<()>),
+    /// Processes the pending batch and every call still queued, then stops the collector
+    Shutdown(oneshot::Sender<()>),
+}
+
+/// State shared by a batcher and its collector task
+struct Core<In, Out> {
+    name: Cow<'static, str>,
+    config: BatchConfigCell,
+    batch_fn: BatchFn<In, Out>,
+    span_fn: SpanFn,
+    /// Held (shared) by every running batch, so flush and shutdown can wait for them
+    inflight: Arc<RwLock<()>>,
+    /// Items sent to the collector whose batch has not started yet
+    queued: AtomicUsize,
+    bisect: Option<Bisect<In, Out>>,
+    /// Weight of an item, every item weighs 1 by default
+    weight: Option<WeightFn<In>>,
+    stats: Stats,
+    load: Mutex<Load>,
+}
+
+/// Receiving end of a collector, counting the calls it received whose batch has not started.
+/// When the collector is dropped along with them (because its runtime shut down), they no longer
+/// count as queued
+struct Inbox<'a, In, Out> {
+    receiver: mpsc::Receiver<Command<In, Out>>,
+    core: &'a Core<In, Out>,
+    items: usize,
+    calls: usize,
+}
+
+impl<In, Out> Inbox<'_, In, Out> {
+    async fn recv(&mut self) -> Option<Command<In, Out>> {
+        let command = self.receiver.recv().await;
+        if let Some(Command::Call(message)) = &command {
+            self.items += message.items.len();
+            self.calls += 1;
+        }
+        command
+    }
+
+    fn started(&mut self, items: usize, calls: usize) {
+        self.items -= items;
+        self.calls -= calls;
+    }
+}
+
+impl<In, Out> Drop for Inbox<'_, In, Out> {
+    fn drop(&mut self) {
+        self.receiver.close();
+        while let Ok(command) = self.receiver.try_recv() {
+            if let Command::Call(message) = command {
+                self.items += message.items.len();
+                self.calls += 1;
+            }
+        }
+
+        self.core.queued.fetch_sub(self.items, Ordering::SeqCst);
+        let pending_calls = &self.core.stats.pending_calls;
+        pending_calls.fetch_sub(self.calls, Ordering::Relaxed);
+    }
+}
+
+/// Room in the queue of pending items reserved by a caller. Released when dropped, e.g. when the
+/// caller stops waiting before its items were sent to the collector
+struct Reservation<'a> {
+    queued: &'a AtomicUsize,
+    /// Items not sent to the collector yet
+    items: usize,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        self.queued.fetch_sub(self.items, Ordering::SeqCst);
+    }
+}
+
+/// Decrements a counter when dropped, e.g. when a batch task completes or is cancelled
+struct DecrementOnDrop<'a>(&'a AtomicUsize);
+
+impl Drop for DecrementOnDrop<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Observed load of a batcher, tuning its [`AdaptiveWindow`]
+#[derive(Default)]
+struct Load {
+    last_arrival: Option<Instant>,
+    /// Moving average of the time between the arrival of two items, in seconds
+    gap: Option<f64>,
+    /// Moving average of the duration of the batch function, in seconds
+    duration: Option<f64>,
+}
+
+impl Load {
+    /// Weight of the latest observation in the moving averages
+    const SMOOTHING: f64 = 0.2;
+
+    fn average(average: &mut Option<f64>, sample: f64) {
+        *average = Some(match *average {
+            Some(average) => average + (sample - average) * Self::SMOOTHING,
+            None => sample,
+        });
+    }
+
+    /// Records the arrival of a call of `items` items. Gaps longer than `cap` are recorded as
+    /// `cap`, so a burst after an idle period is picked up quickly
+    fn arrived(&mut self, items: usize, cap: Duration) {
+        let now = Instant::now();
+        if let Some(last) = self.last_arrival.replace(now) {
+            let gap = (now - last).min(cap).as_secs_f64() / items.max(1) as f64;
+            Self::average(&mut self.gap, gap);
+        }
+    }
+
+    fn completed(&mut self, duration: Duration) {
+        Self::average(&mut self.duration, duration.as_secs_f64());
+    }
+
+    fn window(&self, adaptive: &AdaptiveWindow) -> Duration {
+        adaptive.window(
+            self.gap.map(Duration::from_secs_f64),
+            self.duration.map(Duration::from_secs_f64),
+        )
+    }
+}
+
+/// Re-runs failing batches in halves, see [`BatcherBuilder::split_on_error`]
+struct Bisect<In, Out> {
+    clone_items: fn(&[In]) -> Vec<In>,
+    is_error: fn(&Out) -> bool,
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Core<In, Out> {
+    fn spawn(self: Arc<Self>) -> mpsc::Sender<Command<In, Out>> {
+        let (sender, receiver) = mpsc::channel(1);
+        tokio::task::spawn(self.collect(receiver));
+        sender
+    }
+
+    async fn collect(self: Arc<Self>, receiver: mpsc::Receiver<Command<In, Out>>) {
+        let mut inbox = Inbox {
+            receiver,
+            core: &self,
+            items: 0,
+            calls: 0,
+        };
+        let mut concurrency = ConcurrencyLimit::new(self.config.load().concurrent());
+        let mut bucket = TokenBucket::new();
+        // Call that didn't fit in the previous batch, it starts the next one
+        let mut carried: Option<Message<In, Out>> = None;
+        // After a shutdown, calls that raced it are still queued and are batched without windows
+        let mut closed = false;
+        let mut shut_down = vec![];
+        let mut finished = false;
+
+        while !finished {
+            let mut window_start = Instant::now();
+            let mut batch = Batch::new();
+            let mut flushed = vec![];
+            let mut reason = None;
+
+            while reason.is_none() {
+                let config = self.config.load();
+                if batch.is_full(&config) {
+                    reason = Some(FlushReason::Limit);
+                    break;
+                }
+
+                let command = if let Some(message) = carried.take() {
+                    Some(Command::Call(message))
+                } else if closed || batch.responses.is_empty() {
+                    inbox.recv().await
+                } else {
+                    let window = match &config.adaptive {
+                        Some(adaptive) => self.load.lock().unwrap().window(adaptive),
+                        None => config.window_for(batch.items.len()),
+                    };
+                    // While throttled, the batch keeps collecting until it may start
+                    let window = config.wait_for(batch.items.len(), window);
+                    let window_end = window.map(|window| {
+                        let window_end = window_start + window;
+                        let window_end = match &config.rate {
+                            Some(rate) => window_end.max(bucket.ready_at(rate, config.burst())),
+                            None => window_end,
+                        };
+                        tokio::time::Instant::from_std(window_end)
+                    });
+                    let window_elapsed = async {
+                        match window_end {
+                            Some(window_end) => tokio::time::sleep_until(window_end).await,
+                            None => std::future::pending().await,
+                        }
+                    };
+
+                    tokio::select! {
+                        command = inbox.recv() => command,
+                        _ = window_elapsed => {
+                            reason = Some(FlushReason::Window);
+                            break;
+                        }
+                    }
+                };
+
+                match command {
+                    Some(Command::Call(message)) if batch.fits(&message, &config) => {
+                        if batch.responses.is_empty() {
+                            window_start = Instant::now();
+                        }
+                        if message.priority == Priority::Urgent {
+                            reason = Some(FlushReason::Urgent);
+                        }
+                        batch.push(message);
+                    }
+                    Some(Command::Call(message)) => {
+                        carried = Some(message);
+                        reason = Some(FlushReason::Limit);
+                    }
+                    Some(Command::Flush(done)) => {
+                        flushed.push(done);
+                        reason = Some(FlushReason::Flush);
+                    }
+                    Some(Command::Shutdown(done)) => {
+                        shut_down.push(done);
+                        inbox.receiver.close();
+                        closed = true;
+                    }
+                    None => {
+                        finished = true;
+                        reason = Some(FlushReason::Shutdown);
+                    }
+                }
+            }
+            let reason = reason.unwrap_or(FlushReason::Limit);
+
+            if !batch.responses.is_empty() {
+                metrics::batch_collected(&self.name, reason, batch.items.len(), window_start);
+                let config = self.config.load();
+                let permit = concurrency.acquire(config.concurrent()).await;
+                if let Some(rate) = &config.rate {
+                    bucket.acquire(rate, config.burst()).await;
+                }
+                let inflight = self.inflight.clone().read_owned().await;
+
+                inbox.started(batch.items.len(), batch.responses.len());
+                let queued = self.queued.fetch_sub(batch.items.len(), Ordering::SeqCst);
+                metrics::queue_depth(&self.name, queued - batch.items.len());
+                let stats = &self.stats;
+                stats.batch_started(reason, batch.items.len(), window_start.elapsed());
+                stats
+                    .pending_calls
+                    .fetch_sub(batch.responses.len(), Ordering::Relaxed);
+                stats.inflight_batches.fetch_add(1, Ordering::Relaxed);
+
+                let core = self.clone();
+                tokio::task::spawn(async move {
+                    let _permit = permit;
+                    let _inflight = inflight;
+                    let _running = DecrementOnDrop(&core.stats.inflight_batches);
+                    core.run(batch).await;
+                });
+            }
+
+            for done in flushed {
+                let _ = done.send(());
+            }
+        }
+
+        for done in shut_down {
+            let _ = done.send(());
+        }
+    }
+
+    /// Splits the items of a call into chunks that each fit in a batch (within the limit and
+    /// maximum weight), along with their weight
+    fn chunks(&self, items: Vec<In>) -> Vec<(Vec<In>, u64)> {
+        let config = self.config.load();
+        let (limit, max_weight) = (config.limit().max(1), config.max_weight());
+        let mut chunks = vec![(vec![], 0u64)];
+        for item in items {
+            let weight = self.weight.as_ref().map_or(1, |weight| weight(&item));
+            let (chunk, chunk_weight) = chunks.last_mut().unwrap();
+            if !chunk.is_empty()
+                && (chunk.len() >= limit || chunk_weight.saturating_add(weight) > max_weight)
+            {
+                chunks.push((vec![item], weight));
+            } else {
+                chunk.push(item);
+                *chunk_weight = chunk_weight.saturating_add(weight);
+            }
+        }
+        chunks
+    }
+
+    /// Single chunk holding every item of a call
+    fn weighed(&self, items: Vec<In>) -> (Vec<In>, u64) {
+        let weight = match &self.weight {
+            Some(weight) => items
+                .iter()
+                .fold(0u64, |total, item| total.saturating_add(weight(item))),
+            None => items.len() as u64,
+        };
+        (items, weight)
+    }
+
+    fn arrived(&self, items: usize) {
+        if let Some(adaptive) = self.config.load().adaptive {
+            self.load
+                .lock()
+                .unwrap()
+                .arrived(items, adaptive.max.saturating_mul(2));
+        }
+    }
+
+    /// Waits until every batch started so far has completed
+    async fn wait_inflight(&self) {
+        drop(self.inflight.write().await);
+    }
+
+    async fn run(&self, mut batch: Batch<In, Out>) {
+        if self.config.load().cancel_on_drop {
+            batch.withdraw_cancelled();
+            if batch.responses.is_empty() {
+                return;
+            }
+        }
+
+        let batched_span = (self.span_fn)(batch.items.len());
+        for mut span in batch.spans {
+            TracingSpan::link_span(&mut span, &batched_span);
+        }
+
+        let responses = PendingResponses {
+            responses: batch.responses,
+        };
+        let start = Instant::now();
+        self.execute(batch.items, responses)
+            .instrument(batched_span)
+            .await;
+        metrics::batch_completed(&self.name, start);
+        self.load.lock().unwrap().completed(start.elapsed());
+    }
+
+    /// Runs the batch function and sends its result. With `split_on_error`, a failing batch of
+    /// several callers is split in halves (by caller) which run again on their own
+    fn execute(
+        &self,
+        items: Vec<In>,
+        mut responses: PendingResponses<Out>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(async move {
+            let bisect = self.bisect.as_ref();
+            let retained = bisect
+                .filter(|_| responses.responses.len() > 1)
+                .map(|bisect| (bisect.clone_items)(&items));
+
+            let result = match panic::catch_unwind(AssertUnwindSafe(|| (self.batch_fn)(items))) {
+                Ok(future) => CatchUnwind { future }.await,
+                Err(payload) => Err(payload),
+            };
+
+            match (result, retained) {
+                (Ok(result), Some(mut items)) if bisect.is_some_and(|b| (b.is_error)(&result)) => {
+                    let half = responses.responses.len() / 2;
+                    let second_responses = responses.split_off(half);
+                    let second_items = items.split_off(responses.items());
+
+                    self.execute(items, responses).await;
+                    self.execute(second_items, second_responses).await;
+                }
+                (Ok(result), _) => responses.send(result),
+                (Err(payload), _) => responses.fail(Error::InnerPanicked(panic_message(&*payload))),
+            }
+        })
+    }
+
+    /// Reserves room for `count` items in the queue of pending items
+    fn enqueue(&self, count: usize) -> Result<Reservation<'_>, Error> {
+        let max = self.config.load().queue.unwrap_or(usize::MAX);
+        let queued = self
+            .queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                queued.checked_add(count).filter(|queued| *queued <= max)
+            })
+            .map_err(|_| Error::QueueFull)?;
+
+        metrics::queue_depth(&self.name, queued + count);
+        Ok(Reservation {
+            queued: &self.queued,
+            items: count,
+        })
+    }
+}
+
+/// Groups calls into batches (by time window, size limit and concurrency) and runs a single
+/// batch function for every batch. This is what `#[batched]` expands to.
+///
+/// `F` decides how the batch result is handed out to callers, see [`Batcher::builder`] and
+/// [`Batcher::builder_split`]. Clones share the same executor.
+pub struct Batcher<In, Out, F = Cloned> {
+    shared: Arc<Shared<In, Out>>,
+    _fanout: PhantomData<fn() -> F>,
+}
+
+struct Shared<In, Out> {
+    core: Arc<Core<In, Out>>,
+    state: Mutex<State<In, Out>>,
+}
+
+struct State<In, Out> {
+    /// Channel to the collector task, spawned on the first call (and again after the runtime
+    /// it ran on shut down)
+    sender: Option<mpsc::Sender<Command<In, Out>>>,
+    closed: bool,
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Shared<In, Out> {
+    fn sender(&self) -> Result<mpsc::Sender<Command<In, Out>>, Error> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Err(Error::ExecutorClosed);
+        }
+
+        // The collector is gone when the runtime it was spawned on shut down (e.g. a previous
+        // `#[tokio::test]`), it's spawned again on the runtime of the caller
+        if state
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.is_closed())
+        {
+            state.sender = None;
+        }
+        let sender = state
+            .sender
+            .get_or_insert_with(|| self.core.clone().spawn());
+        Ok(sender.clone())
+    }
+
+    async fn flush(&self) {
+        let sender = self.state.lock().unwrap().sender.clone();
+        if let Some(sender) = sender {
+            Self::command(sender, Command::Flush).await;
+        }
+        self.core.wait_inflight().await;
+    }
+
+    async fn shutdown(&self) {
+        let sender = {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            state.sender.take()
+        };
+
+        if let Some(sender) = sender {
+            Self::command(sender, Command::Shutdown).await;
+        }
+        self.core.wait_inflight().await;
+    }
+
+    /// Sends a command to the collector and waits for it to be handled, the collector may
+    /// already be gone after a shutdown
+    async fn command(
+        sender: mpsc::Sender<Command<In, Out>>,
+        command: fn(oneshot::Sender<()>) -> Command<In, Out>,
+    ) {
+        let (done, handled) = oneshot::channel();
+        if sender.send(command(done)).await.is_ok() {
+            let _ = handled.await;
+        }
+    }
+}
+
+/// Type erased batcher, used to shut down every batcher at once
+trait Shutdown: Send + Sync {
+    fn boxed_shutdown(self: Arc<Self>) -> BoxFuture<()>;
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Shutdown for Shared<In, Out> {
+    fn boxed_shutdown(self: Arc<Self>) -> BoxFuture<()> {
+        Box::pin(async move { self.shutdown().await })
+    }
+}
+
+/// Every batcher built so far
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    batchers: Vec::new(),
+    closed: false,
+});
+
+struct Registry {
+    batchers: Vec<Weak<dyn Shutdown>>,
+    /// Set by [`shutdown_all`], batchers built afterwards start shut down
+    closed: bool,
+}
+
+impl Registry {
+    fn register<In: Send + 'static, Out: Send + 'static>(shared: &Arc<Shared<In, Out>>) {
+        let mut registry = REGISTRY.lock().unwrap();
+        if registry.closed {
+            shared.state.lock().unwrap().closed = true;
+            return;
+        }
+
+        // Dropped batchers are only cleaned up when the list would grow, keeping this amortized
+        if registry.batchers.len() == registry.batchers.capacity() {
+            registry
+                .batchers
+                .retain(|batcher| batcher.strong_count() > 0);
+        }
+        let shared: Arc<dyn Shutdown> = shared.clone();
+        registry.batchers.push(Arc::downgrade(&shared));
+    }
+}
+
+/// Shuts down every batcher (including the executors of every `#[batched]` function), see
+/// [`Batcher::shutdown`]. Batchers created afterwards reject calls as well.
+pub async fn shutdown_all() {
+    let batchers: Vec<_> = {
+        let mut registry = REGISTRY.lock().unwrap();
+        registry.closed = true;
+        registry
+            .batchers
+            .drain(..)
+            .filter_map(|batcher| batcher.upgrade())
+            .collect()
+    };
+
+    for batcher in batchers {
+        batcher.boxed_shutdown().await;
+    }
+}
+
+impl<In, Out, F> Clone for Batcher<In, Out, F> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+            _fanout: PhantomData,
+        }
+    }
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Batcher<In, Out, Cloned> {
+    /// Batcher where every caller receives a clone of the batch result
+    pub fn builder<Func, Fut>(batch_fn: Func) -> BatcherBuilder<In, Out, Cloned>
+    where
+        Func: Fn(Vec<In>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Out> + Send + 'static,
+    {
+        BatcherBuilder::new(batch_fn)
+    }
+}
+
+impl<In: Send + 'static, Out: SplitOutput + Send + 'static> Batcher<In, Out, Split> {
+    /// Batcher where every caller receives the results of its own items, the batch function
+    /// must return one result per item in the same order as the input
+    pub fn builder_split<Func, Fut>(batch_fn: Func) -> BatcherBuilder<In, Out, Split>
+    where
+        Func: Fn(Vec<In>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Out> + Send + 'static,
+    {
+        BatcherBuilder::new(batch_fn)
+    }
+}
+
+impl<In: Send + 'static, Out: Send + 'static, F> Batcher<In, Out, F> {
+    /// Configuration of the batcher, changes apply from the next batch
+    pub fn config(&self) -> &BatchConfigCell {
+        &self.shared.core.config
+    }
+
+    /// Adds a single item to the next batch and waits for its result, panics if the batching
+    /// fails (see [`Batcher::try_call`])
+    pub async fn call(&self, item: In) -> F::Single
+    where
+        F: Fanout<Out>,
+    {
+        self.call_with_priority(item, Priority::Normal).await
+    }
+
+    /// [`Batcher::call`] with the given priority, see [`Priority`]
+    pub async fn call_with_priority(&self, item: In, priority: Priority) -> F::Single
+    where
+        F: Fanout<Out>,
+    {
+        self.try_call_with_priority(item, priority)
+            .await
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Adds all items to the next batch and waits for their result, panics if the batching
+    /// fails (see [`Batcher::try_call_many`])
+    pub async fn call_many(&self, items: Vec<In>) -> Out
+    where
+        F: Fanout<Out>,
+    {
+        self.call_many_with_priority(items, Priority::Normal).await
+    }
+
+    /// [`Batcher::call_many`] with the given priority, see [`Priority`]
+    pub async fn call_many_with_priority(&self, items: Vec<In>, priority: Priority) -> Out
+    where
+        F: Fanout<Out>,
+    {
+        self.try_call_many_with_priority(items, priority)
+            .await
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Adds all items to the next batch without waiting for the batch to run, panics if the
+    /// batcher rejects them (see [`Batcher::try_fire_and_forget`])
+    pub async fn fire_and_forget(&self, items: Vec<In>) {
+        self.fire_and_forget_with_priority(items, Priority::Normal)
+            .await
+    }
+
+    /// [`Batcher::fire_and_forget`] with the given priority, see [`Priority`]
+    pub async fn fire_and_forget_with_priority(&self, items: Vec<In>, priority: Priority) {
+        self.try_fire_and_forget_with_priority(items, priority)
+            .await
+            .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Adds a single item to the next batch and waits for its result
+    pub async fn try_call(&self, item: In) -> Result<F::Single, Error>
+    where
+        F: Fanout<Out>,
+    {
+        self.try_call_with_priority(item, Priority::Normal).await
+    }
+
+    /// [`Batcher::try_call`] with the given priority, see [`Priority`]
+    pub async fn try_call_with_priority(
+        &self,
+        item: In,
+        priority: Priority,
+    ) -> Result<F::Single, Error>
+    where
+        F: Fanout<Out>,
+    {
+        self.try_call_many_with_priority(vec![item], priority)
+            .await
+            .map(F::single)
+    }
+
+    /// Adds all items to the next batch and waits for their result. Items exceeding the limit or
+    /// maximum weight of a batch are spread over consecutive batches (possibly running
+    /// concurrently), whose results are merged in order (unless the results can't be merged, see
+    /// [`Fanout::MERGE`]).
+    ///
+    /// The queue holds every item of the call or none of them, so [`Error::QueueFull`] means no
+    /// item was processed. Other errors (e.g. the batcher shutting down while the chunks are
+    /// sent) may fail a call whose first chunks are still processed
+    pub async fn try_call_many(&self, items: Vec<In>) -> Result<Out, Error>
+    where
+        F: Fanout<Out>,
+    {
+        self.try_call_many_with_priority(items, Priority::Normal)
+            .await
+    }
+
+    /// [`Batcher::try_call_many`] with the given priority, see [`Priority`]
+    pub async fn try_call_many_with_priority(
+        &self,
+        items: Vec<In>,
+        priority: Priority,
+    ) -> Result<Out, Error>
+    where
+        F: Fanout<Out>,
+    {
+        let call = async {
+            let core = &self.shared.core;
+            let chunks = match F::MERGE {
+                Some(_) => core.chunks(items),
+                None => vec![core.weighed(items)],
+            };
+
+            let mut receivers = vec![];
+            self.send_chunks(chunks, |items, weight| {
+                let (sender, receiver) = oneshot::channel();
+                receivers.push(receiver);
+                Message {
+                    items,
+                    weight,
+                    priority,
+                    span: Span::current(),
+                    response: Some(Response {
+                        sender,
+                        split: F::split,
+                        results: F::results,
+                    }),
+                }
+            })
+            .await?;
+
+            let mut result: Option<Out> = None;
+            for receiver in receivers {
+                // Only dropped without a response if the collector is gone
+                let chunk = receiver.await.unwrap_or(Err(Error::ExecutorClosed))?;
+                match (&mut result, F::MERGE) {
+                    (Some(result), Some(merge)) => merge(result, chunk),
+                    _ => result = Some(chunk),
+                }
+            }
+            Ok(result.expect("calls are sent as at least one chunk"))
+        };
+
+        match self.config().load().timeout {
+            Some(timeout) => tokio::time::timeout(timeout, call)
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+            None => call.await,
+        }
+    }
+
+    /// Adds all items to the next batch without waiting for the batch to run. Items exceeding the
+    /// limit or maximum weight of a batch are spread over consecutive batches, as with
+    /// [`Batcher::try_call_many`] the queue holds all of them or none
+    pub async fn try_fire_and_forget(&self, items: Vec<In>) -> Result<(), Error> {
+        self.try_fire_and_forget_with_priority(items, Priority::Normal)
+            .await
+    }
+
+    /// [`Batcher::try_fire_and_forget`] with the given priority, see [`Priority`]
+    pub async fn try_fire_and_forget_with_priority(
+        &self,
+        items: Vec<In>,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        let chunks = self.shared.core.chunks(items);
+        self.send_chunks(chunks, |items, weight| Message {
+            items,
+            weight,
+            priority,
+            span: Span::current(),
+            response: None,
+        })
+        .await
+    }
+
+    /// Processes the pending batch right away and waits until every batch started so far has
+    /// completed
+    pub async fn flush(&self) {
+        self.shared.flush().await;
+    }
+
+    /// Processes every pending call and waits for running batches, calls made afterwards fail
+    /// with [`Error::ExecutorClosed`]
+    pub async fn shutdown(&self) {
+        self.shared.shutdown().await;
+    }
+
+    /// Snapshot of the statistics of the batcher
+    pub fn stats(&self) -> BatchStats {
+        let core = &self.shared.core;
+        core.stats.snapshot(core.queued.load(Ordering::SeqCst))
+    }
+
+    /// Whether the batcher has no other clone, pending call or running batch
+    fn is_idle(&self) -> bool {
+        let core = &self.shared.core;
+        Arc::strong_count(&self.shared) == 1
+            && core.queued.load(Ordering::SeqCst) == 0
+            && core.stats.pending_calls.load(Ordering::Relaxed) == 0
+            && core.stats.inflight_batches.load(Ordering::Relaxed) == 0
+    }
+
+    pub fn is_shut_down(&self) -> bool {
+        self.shared.state.lock().unwrap().closed
+    }
+
+    /// Sends the chunks of a call to the collector as messages built by `message`. Room is
+    /// reserved for every chunk upfront, so a full queue rejects the whole call
+    async fn send_chunks(
+        &self,
+        chunks: Vec<(Vec<In>, u64)>,
+        mut message: impl FnMut(Vec<In>, u64) -> Message<In, Out>,
+    ) -> Result<(), Error> {
+        let sender = self.shared.sender()?;
+        let count = chunks.iter().map(|(items, _)| items.len()).sum();
+        let mut reservation = self.shared.core.enqueue(count)?;
+        for (items, weight) in chunks {
+            self.submit(&sender, message(items, weight), &mut reservation)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Sends a call to the collector, which takes over its room in the queue from `reservation`.
+    /// If the caller stops waiting (or the collector is gone) before, the call no longer counts
+    /// as pending
+    async fn submit(
+        &self,
+        sender: &mpsc::Sender<Command<In, Out>>,
+        message: Message<In, Out>,
+        reservation: &mut Reservation<'_>,
+    ) -> Result<(), Error> {
+        let core = &self.shared.core;
+        let count = message.items.len();
+        core.stats.pending_calls.fetch_add(1, Ordering::Relaxed);
+        let pending_call = DecrementOnDrop(&core.stats.pending_calls);
+        core.arrived(count);
+
+        sender
+            .send(Command::Call(message))
+            .await
+            .map_err(|_| Error::ExecutorClosed)?;
+        std::mem::forget(pending_call);
+        reservation.items -= count;
+        Ok(())
+    }
+}
+
+pub struct BatcherBuilder<In, Out, F> {
+    batch_fn: BatchFn<In, Out>,
+    config: BatchConfig,
+    config_cell: Option<BatchConfigCell>,
+    name: Cow<'static, str>,
+    span_fn: Option<SpanFn>,
+    bisect: Option<Bisect<In, Out>>,
+    weight: Option<WeightFn<In>>,
+    _fanout: PhantomData<fn() -> F>,
+}
+
+impl<In: Send + 'static, Out: Send + 'static, F> BatcherBuilder<In, Out, F> {
+    fn new<Func, Fut>(batch_fn: Func) -> Self
+    where
+        Func: Fn(Vec<In>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Out> + Send + 'static,
+    {
+        let batch_fn: BatchFn<In, Out> = Arc::new(move |items| Box::pin(batch_fn(items)));
+        Self {
+            batch_fn,
+            config: BatchConfig::new(Duration::ZERO),
+            config_cell: None,
+            name: Cow::Borrowed("batched"),
+            span_fn: None,
+            bisect: None,
+            weight: None,
+            _fanout: PhantomData,
+        }
+    }
+
+    /// Time the executor waits after the first call before processing a batch (default: zero,
+    /// only calls queued at the same time are grouped)
+    pub fn window(mut self, window: Duration) -> Self {
+        self.config.window = window;
+        self
+    }
+
+    /// Window used while the batch holds at most `len` items
+    pub fn window_for(mut self, len: u64, window: Duration) -> Self {
+        self.config.windows.insert(len, window);
+        self
+    }
+
+    /// Tunes the window to the observed load instead of using `window` and `window_for`
+    pub fn adaptive(mut self, adaptive: AdaptiveWindow) -> Self {
+        self.config.adaptive = Some(adaptive);
+        self
+    }
+
+    /// Maximum frequency at which batches start (e.g. `Rate::per_second(10)`), items keep
+    /// accumulating into larger batches while throttled
+    pub fn rate(mut self, rate: Rate) -> Self {
+        self.config.rate = Some(rate);
+        self
+    }
+
+    /// Batches that may start back to back before `rate` applies (default: 1)
+    pub fn burst(mut self, burst: usize) -> Self {
+        self.config.burst = Some(burst);
+        self
+    }
+
+    /// Keeps collecting past the window until the batch holds at least `min` items, see
+    /// [`BatcherBuilder::max_wait`]
+    pub fn min(mut self, min: usize) -> Self {
+        self.config.min = Some(min);
+        self
+    }
+
+    /// Starts a batch below `min` anyway once `max_wait` has elapsed since its first item
+    /// (default: waits until it reaches `min`, the batcher is flushed or shut down)
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.config.max_wait = Some(max_wait);
+        self
+    }
+
+    /// Weight of an item, counted against [`BatcherBuilder::max_weight`] (default: 1 per item)
+    pub fn weight(mut self, weight: impl Fn(&In) -> u64 + Send + Sync + 'static) -> Self {
+        self.weight = Some(Arc::new(weight));
+        self
+    }
+
+    /// Maximum cumulative weight of the items of a batch. A batch starts when the next call would
+    /// exceed it, and calls heavier than it are spread over several batches when their results
+    /// can be merged ([`Split`])
+    pub fn max_weight(mut self, max_weight: u64) -> Self {
+        self.config.max_weight = Some(max_weight);
+        self
+    }
+
+    /// Maximum amount of items processed in a single batch. Calls with more items are spread over
+    /// several batches when their results can be merged ([`Split`])
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.config.limit = Some(limit);
+        self
+    }
+
+    /// Maximum amount of batches running concurrently
+    pub fn concurrent(mut self, concurrent: usize) -> Self {
+        self.config.concurrent = Some(concurrent);
+        self
+    }
+
+    /// Maximum time a call waits for its result before failing with [`Error::Timeout`]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Maximum amount of items waiting for their batch to start, calls beyond it fail with
+    /// [`Error::QueueFull`]
+    pub fn queue(mut self, queue: usize) -> Self {
+        self.config.queue = Some(queue);
+        self
+    }
+
+    /// Withdraws the items of callers that were dropped (e.g. by a timeout or an aborted request)
+    /// before their batch started
+    pub fn cancel_on_drop(mut self, cancel_on_drop: bool) -> Self {
+        self.config.cancel_on_drop = cancel_on_drop;
+        self
+    }
+
+    /// Uses (and shares) an existing configuration, replacing any window, limit or concurrency
+    /// set on the builder
+    pub fn config(mut self, config: BatchConfigCell) -> Self {
+        self.config_cell = Some(config);
+        self
+    }
+
+    /// Name of the batcher, used in the tracing span of every batch and as label of its metrics
+    pub fn name(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Creates the tracing span of a batch from the amount of items in the batch
+    pub fn span(mut self, span: impl Fn(usize) -> Span + Send + Sync + 'static) -> Self {
+        self.span_fn = Some(Arc::new(span));
+        self
+    }
+
+    pub fn build(self) -> Batcher<In, Out, F> {
+        let name = self.name.clone();
+        let span_fn = self
+            .span_fn
+            .unwrap_or_else(|| Arc::new(move |count| info_span!("batched", name = %name, count)));
+
+        let core = Core {
+            name: self.name,
+            config: self
+                .config_cell
+                .unwrap_or_else(|| BatchConfigCell::new(self.config)),
+            batch_fn: self.batch_fn,
+            span_fn,
+            inflight: Arc::default(),
+            queued: AtomicUsize::new(0),
+            bisect: self.bisect,
+            weight: self.weight,
+            stats: Stats::default(),
+            load: Mutex::default(),
+        };
+
+        let shared = Arc::new(Shared {
+            core: Arc::new(core),
+            state: Mutex::new(State {
+                sender: None,
+                closed: false,
+            }),
+        });
+        Registry::register(&shared);
+
+        Batcher {
+            shared,
+            _fanout: PhantomData,
+        }
+    }
+}
+
+impl<In: Hash + Eq + Send + 'static, Out: Send + 'static, F: Dedupe<Out>>
+    BatcherBuilder<In, Out, F>
+{
+    /// Collapses duplicate items of a batch before calling the batch function, every caller
+    /// still receives the result of each of its items
+    pub fn dedupe(mut self) -> Self {
+        let batch_fn = self.batch_fn;
+        self.batch_fn = Arc::new(move |items: Vec<In>| {
+            let count = items.len();
+            let mut positions = HashMap::with_capacity(count);
+            let indices: Vec<usize> = items
+                .into_iter()
+                .map(|item| {
+                    let next = positions.len();
+                    *positions.entry(item).or_insert(next)
+                })
+                .collect();
+
+            let mut unique: Vec<_> = positions.into_iter().map(|(item, i)| (i, item)).collect();
+            unique.sort_unstable_by_key(|(index, _)| *index);
+            let unique = unique.into_iter().map(|(_, item)| item).collect::<Vec<_>>();
+            if unique.len() == count {
+                return batch_fn(unique);
+            }
+
+            let result = batch_fn(unique);
+            Box::pin(async move { F::expand(result.await, &indices) })
+        });
+        self
+    }
+}
+
+impl<In: Clone + Send + 'static, T: Send + 'static, E: Send + 'static, F>
+    BatcherBuilder<In, Result<T, E>, F>
+{
+    /// Re-runs the whole batch while it fails and `retry` allows another attempt, callers only
+    /// receive the result of the last attempt
+    pub fn retry(mut self, retry: Retry<E>) -> Self {
+        let batch_fn = self.batch_fn;
+        self.batch_fn = Arc::new(move |items: Vec<In>| {
+            let batch_fn = batch_fn.clone();
+            let retry = retry.clone();
+            Box::pin(async move {
+                let mut attempt = 1;
+                loop {
+                    let result = batch_fn(items.clone()).await;
+                    if !matches!(&result, Err(error) if retry.retries(attempt, error)) {
+                        return result;
+                    }
+                    tokio::time::sleep(retry.delay(attempt)).await;
+                    attempt += 1;
+                }
+            })
+        });
+        self
+    }
+
+    /// When the batch fails, splits it in halves (by caller) and runs each half again, until
+    /// the failing callers are isolated. Callers in a failing batch of their own receive the
+    /// error, every other caller receives the result of the half it ended up in
+    pub fn split_on_error(mut self) -> Self {
+        self.bisect = Some(Bisect {
+            clone_items: <[In]>::to_vec,
+            is_error: Result::is_err,
+        });
+        self
+    }
+}
+
+/// Key of the executor of a batched method, the value of its `instance` attribute. Keys holding
+/// values of different types are never equal.
+pub struct InstanceKey(Box<dyn DynKey>);
+
+impl InstanceKey {
+    pub fn new<T: Hash + Eq + Send + Sync + 'static>(value: T) -> Self {
+        Self(Box::new(value))
+    }
+}
+
+impl PartialEq for InstanceKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.dyn_eq(other.0.as_any())
+    }
+}
+
+impl Eq for InstanceKey {}
+
+impl Hash for InstanceKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.dyn_hash(state);
+    }
+}
+
+/// Object safe `Hash + Eq`
+trait DynKey: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn dyn_eq(&self, other: &dyn Any) -> bool;
+    fn dyn_hash(&self, state: &mut dyn Hasher);
+}
+
+impl<T: Hash + Eq + Send + Sync + 'static> DynKey for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn dyn_eq(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>() == Some(self)
+    }
+
+    fn dyn_hash(&self, mut state: &mut dyn Hasher) {
+        self.hash(&mut state);
+    }
+}
+
+/// Batchers created on demand for every key, e.g. one per partition key. This is what
+/// `#[batched]` functions with a partition key (or methods) keep their executors in.
+pub struct BatcherMap<K, In, Out, F = Cloned> {
+    state: Mutex<MapState<K, In, Out, F>>,
+}
+
+struct MapState<K, In, Out, F> {
+    batchers: HashMap<K, Batcher<In, Out, F>>,
+    /// Statistics of the evicted batchers
+    evicted: BatchStats,
+    closed: bool,
+}
+
+impl<K, In, Out, F> Default for BatcherMap<K, In, Out, F> {
+    fn default() -> Self {
+        Self {
+            state: Mutex::new(MapState {
+                batchers: HashMap::new(),
+                evicted: BatchStats::default(),
+                closed: false,
+            }),
+        }
+    }
+}
+
+impl<K: Hash + Eq, In: Send + 'static, Out: Send + 'static, F> BatcherMap<K, In, Out, F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Batcher of `key`, built with `build` if there is none yet. After a shutdown new keys get a
+    /// batcher that is already shut down.
+    ///
+    /// Batchers that are idle (not used elsewhere, without pending calls or running batches) are
+    /// evicted as new keys are added, stopping their collector task, so the map only grows with
+    /// the amount of keys in use at the same time. A key called again gets a new batcher.
+    pub fn get_or_insert_with(
+        &self,
+        key: K,
+        build: impl FnOnce() -> Batcher<In, Out, F>,
+    ) -> Batcher<In, Out, F> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed && !state.batchers.contains_key(&key) {
+            let batcher = build();
+            batcher.shared.state.lock().unwrap().closed = true;
+            return batcher;
+        }
+
+        // Idle batchers (e.g. of keys no longer called) are only evicted when the map would grow,
+        // keeping this amortized
+        if state.batchers.len() == state.batchers.capacity() && !state.batchers.contains_key(&key) {
+            let MapState {
+                batchers, evicted, ..
+            } = &mut *state;
+            batchers.retain(|_, batcher| {
+                let idle = batcher.is_idle();
+                if idle {
+                    evicted.merge(&batcher.stats());
+                }
+                !idle
+            });
+        }
+        state.batchers.entry(key).or_insert_with(build).clone()
+    }
+
+    /// Amount of batchers, i.e. of keys in use
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().batchers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Flushes every batcher, see [`Batcher::flush`]
+    pub async fn flush(&self) {
+        for batcher in self.batchers() {
+            batcher.flush().await;
+        }
+    }
+
+    /// Shuts down every batcher and rejects new keys, see [`Batcher::shutdown`]
+    pub async fn shutdown(&self) {
+        self.state.lock().unwrap().closed = true;
+        for batcher in self.batchers() {
+            batcher.shutdown().await;
+        }
+    }
+
+    /// Statistics of every batcher combined, including the evicted ones
+    pub fn stats(&self) -> BatchStats {
+        let mut stats = self.state.lock().unwrap().evicted.clone();
+        for batcher in self.batchers() {
+            stats.merge(&batcher.stats());
+        }
+        stats
+    }
+
+    fn batchers(&self) -> Vec<Batcher<In, Out, F>> {
+        let state = self.state.lock().unwrap();
+        state.batchers.values().cloned().collect()
+    }
+}
+
+/// Semaphore bounding the amount of concurrent batches, resizable between batches
+struct ConcurrencyLimit {
+    semaphore: Arc<Semaphore>,
+    permits: usize,
+    /// Permits that still have to be forgotten after the limit was lowered
+    debt: usize,
+}
+
+impl ConcurrencyLimit {
+    fn new(permits: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(permits)),
+            permits,
+            debt: 0,
+        }
+    }
+
+    /// Waits until a batch may run with at most `permits` batches running concurrently
+    async fn acquire(&mut self, permits: usize) -> OwnedSemaphorePermit {
+        if permits > self.permits {
+            let mut added = permits - self.permits;
+            let repaid = added.min(self.debt);
+            self.debt -= repaid;
+            added -= repaid;
+            self.semaphore.add_permits(added);
+        } else {
+            self.debt += self.permits - permits;
+        }
+        self.permits = permits;
+
+        while self.debt > 0 {
+            self.debt -= self.semaphore.forget_permits(self.debt);
+            if self.debt > 0 {
+                let permit = self.semaphore.acquire().await.unwrap();
+                permit.forget();
+                self.debt -= 1;
+            }
+        }
+
+        self.semaphore.clone().acquire_owned().await.unwrap()
+    }
+}
+
+/// Token bucket bounding how often batches start, see [`BatcherBuilder::rate`]
+struct TokenBucket {
+    /// Tokens left, full (`burst`) until the first batch
+    tokens: Option<f64>,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new() -> Self {
+        Self {
+            tokens: None,
+            refilled: Instant::now(),
+        }
+    }
+
+    /// Refills the bucket and returns when the next token is available
+    fn ready_at(&mut self, rate: &Rate, burst: usize) -> Instant {
+        let now = Instant::now();
+        let interval = rate.interval().as_secs_f64();
+        let elapsed = (now - self.refilled).as_secs_f64();
+        let tokens = self.tokens.unwrap_or(burst as f64);
+        let tokens = match interval > 0.0 {
+            true => (tokens + elapsed / interval).min(burst as f64),
+            false => burst as f64,
+        };
+        self.tokens = Some(tokens);
+        self.refilled = now;
+
+        match tokens >= 1.0 {
+            true => now,
+            false => now + Duration::from_secs_f64((1.0 - tokens) * interval),
+        }
+    }
+
+    /// Waits for a token and takes it
+    async fn acquire(&mut self, rate: &Rate, burst: usize) {
+        let ready = self.ready_at(rate, burst);
+        tokio::time::sleep_until(tokio::time::Instant::from_std(ready)).await;
+        self.ready_at(rate, burst);
+        self.tokens = self.tokens.map(|tokens| (tokens - 1.0).max(0.0));
+    }
+}
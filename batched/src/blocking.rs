@@ -0,0 +1,33 @@
+use std::sync::LazyLock;
+
+use tokio::runtime::{Builder, Handle};
+
+/// Runtime of callers outside of any runtime, driven by a dedicated background thread
+static BACKGROUND: LazyLock<Handle> = LazyLock::new(|| {
+    let runtime = Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the batched background runtime");
+    let handle = runtime.handle().clone();
+
+    std::thread::Builder::new()
+        .name("batched".into())
+        .spawn(move || runtime.block_on(std::future::pending::<()>()))
+        .expect("failed to spawn the batched background thread");
+    handle
+});
+
+/// Blocks the current thread until `future` completes, for synchronous callers (e.g. rayon
+/// workers or FFI callbacks). The future runs on the current runtime if there is one (e.g. in
+/// `tokio::task::spawn_blocking`), otherwise on a runtime driven by a background thread, where
+/// the executors of the batchers it calls are spawned. Those executors stay on that runtime, so
+/// later asynchronous callers of the same batchers have their batches run there as well.
+///
+/// # Panics
+/// When called from asynchronous code, which would block the runtime
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    match Handle::try_current() {
+        Ok(handle) => handle.block_on(future),
+        Err(_) => BACKGROUND.block_on(future),
+    }
+}
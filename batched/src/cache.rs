@@ -0,0 +1,209 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    hash::Hash,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::error::Error;
+
+/// Results of a batched function kept for `ttl`, so repeated lookups don't reach the batched
+/// function. When full, the oldest entries are evicted first
+pub struct Cache<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    state: Mutex<State<K, V>>,
+}
+
+struct State<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// Keys by insertion sequence, i.e. in insertion (and thus expiry) order
+    order: BTreeMap<u64, K>,
+    sequence: u64,
+    /// Incremented by every invalidation, loads started before are not cached
+    epoch: u64,
+}
+
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    sequence: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> Cache<K, V> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                order: BTreeMap::new(),
+                sequence: 0,
+                epoch: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.get(key)?;
+        if entry.inserted.elapsed() < self.ttl {
+            return Some(entry.value.clone());
+        }
+
+        state.remove(key);
+        None
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut state = self.state.lock().unwrap();
+        let epoch = state.epoch;
+        self.insert_at(&mut state, epoch, key, value);
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        let mut state = self.state.lock().unwrap();
+        state.epoch += 1;
+        state.remove(key);
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.epoch += 1;
+        state.entries.clear();
+        state.order.clear();
+    }
+
+    /// Cached value of `key`, or the value returned by `load` which is cached
+    pub async fn get_or_load(&self, key: K, load: impl Future<Output = V>) -> V {
+        let result = self.try_get_or_load(key, async { Ok::<_, Infallible>(load.await) });
+        match result.await {
+            Ok(value) => value,
+            Err(error) => match error {},
+        }
+    }
+
+    /// Cached value of `key`, or the result of `load` which is cached if it succeeds
+    pub async fn try_get_or_load<E>(
+        &self,
+        key: K,
+        load: impl Future<Output = Result<V, E>>,
+    ) -> Result<V, E> {
+        if let Some(value) = self.get(&key) {
+            return Ok(value);
+        }
+
+        let epoch = self.epoch();
+        let value = load.await?;
+        let mut state = self.state.lock().unwrap();
+        self.insert_at(&mut state, epoch, key, value.clone());
+        Ok(value)
+    }
+
+    /// Values of every item, `load` is only called with the items missing from the cache (in
+    /// order) and must return one value per item. Fails with [`Error::MissingResults`] when it
+    /// returns fewer values
+    pub async fn get_or_load_many<T>(
+        &self,
+        items: Vec<T>,
+        key: impl Fn(&T) -> K,
+        load: impl AsyncFnOnce(Vec<T>) -> Vec<V>,
+    ) -> Result<Vec<V>, Error> {
+        let load = async |items| Ok::<_, Infallible>(load(items).await);
+        let values = self.try_get_or_load_many(items, key, load).await?;
+        match values {
+            Ok(values) => Ok(values),
+            Err(error) => match error {},
+        }
+    }
+
+    /// Values of every item, `load` is only called with the items missing from the cache (in
+    /// order) and its values are cached if it succeeds. Fails with [`Error::MissingResults`] when
+    /// it returns fewer values than items
+    pub async fn try_get_or_load_many<T, E>(
+        &self,
+        items: Vec<T>,
+        key: impl Fn(&T) -> K,
+        load: impl AsyncFnOnce(Vec<T>) -> Result<Vec<V>, E>,
+    ) -> Result<Result<Vec<V>, E>, Error> {
+        let mut values = Vec::with_capacity(items.len());
+        let mut missing = vec![];
+        for item in items {
+            let value = self.get(&key(&item));
+            if value.is_none() {
+                missing.push(item);
+            }
+            values.push(value);
+        }
+
+        if missing.is_empty() {
+            return Ok(Ok(values.into_iter().flatten().collect()));
+        }
+
+        let keys: Vec<K> = missing.iter().map(key).collect();
+        let epoch = self.epoch();
+        let loaded = match load(missing).await {
+            Ok(loaded) => loaded,
+            Err(error) => return Ok(Err(error)),
+        };
+        if loaded.len() < keys.len() {
+            return Err(Error::MissingResults {
+                expected: keys.len(),
+                returned: loaded.len(),
+            });
+        }
+
+        let mut state = self.state.lock().unwrap();
+        for (key, value) in keys.into_iter().zip(&loaded) {
+            self.insert_at(&mut state, epoch, key, value.clone());
+        }
+        drop(state);
+
+        let mut loaded = loaded.into_iter();
+        let values = values.into_iter();
+        let values = values.map(|value| value.or_else(|| loaded.next()));
+        Ok(Ok(values.flatten().collect()))
+    }
+
+    fn epoch(&self) -> u64 {
+        self.state.lock().unwrap().epoch
+    }
+
+    /// Caches a value loaded since `epoch`, unless the cache was invalidated in the meantime
+    fn insert_at(&self, state: &mut State<K, V>, epoch: u64, key: K, value: V) {
+        if self.capacity == 0 || state.epoch != epoch {
+            return;
+        }
+
+        state.remove(&key);
+        state.sequence += 1;
+        let sequence = state.sequence;
+        state.order.insert(sequence, key.clone());
+        let inserted = Instant::now();
+        let entry = Entry {
+            value,
+            inserted,
+            sequence,
+        };
+        state.entries.insert(key, entry);
+
+        while let Some(entry) = state.order.first_entry() {
+            let inserted = state.entries[entry.get()].inserted;
+            if inserted.elapsed() < self.ttl && state.entries.len() <= self.capacity {
+                break;
+            }
+
+            let key = entry.remove();
+            state.entries.remove(&key);
+        }
+    }
+}
+
+impl<K: Hash + Eq, V> State<K, V> {
+    fn remove(&mut self, key: &K) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.sequence);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use tokio::sync::Semaphore;
+
+/// Batching parameters of a batched function. The executor reads them at the start of every
+/// batch, so changes made through [`BatchConfigCell`] apply from the next batch onwards
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchConfig {
+    /// Time the executor waits after the first call before processing a batch
+    pub window: Duration,
+    /// Window used while the buffer holds at most `N` items, overrides [`BatchConfig::window`]
+    pub windows: BTreeMap<u64, Duration>,
+    /// Maximum amount of items processed in a single batch, larger calls are split
+    pub limit: Option<usize>,
+    /// Minimum amount of items of a batch, the executor keeps collecting past the window until
+    /// the batch reaches it (or `max_wait` elapses)
+    pub min: Option<usize>,
+    /// Maximum time since the first item a batch below `min` waits for more items
+    pub max_wait: Option<Duration>,
+    /// Maximum cumulative weight of the items of a batch, see [`BatcherBuilder::weight`]
+    ///
+    /// [`BatcherBuilder::weight`]: crate::BatcherBuilder::weight
+    pub max_weight: Option<u64>,
+    /// Maximum amount of batches running concurrently
+    pub concurrent: Option<usize>,
+    /// Maximum frequency at which batches start, items keep accumulating while throttled
+    pub rate: Option<Rate>,
+    /// Batches that may start back to back before `rate` applies
+    pub burst: Option<usize>,
+    /// Withdraws the items of callers that were dropped before their batch started
+    pub cancel_on_drop: bool,
+    /// Maximum time a call waits for its result
+    pub timeout: Option<Duration>,
+    /// Maximum amount of items waiting for their batch to start, calls beyond it are rejected
+    pub queue: Option<usize>,
+    /// Window adapting to the observed load, replaces `window` and `windows` when set
+    pub adaptive: Option<AdaptiveWindow>,
+}
+
+impl BatchConfig {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            windows: BTreeMap::new(),
+            limit: None,
+            min: None,
+            max_wait: None,
+            max_weight: None,
+            concurrent: None,
+            rate: None,
+            burst: None,
+            cancel_on_drop: false,
+            timeout: None,
+            queue: None,
+            adaptive: None,
+        }
+    }
+
+    /// Window of a batch currently holding `len` items
+    pub fn window_for(&self, len: usize) -> Duration {
+        self.windows
+            .iter()
+            .find(|(max_calls, _)| **max_calls >= len as u64)
+            .map(|(_, window)| *window)
+            .unwrap_or(self.window)
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(usize::MAX)
+    }
+
+    /// Time after the first item of a batch holding `len` items when the batch starts, `None` if
+    /// it waits until it reaches `min`
+    pub fn wait_for(&self, len: usize, window: Duration) -> Option<Duration> {
+        match self.min {
+            Some(min) if len < min => self.max_wait.map(|max_wait| max_wait.max(window)),
+            _ => Some(window),
+        }
+    }
+
+    pub fn max_weight(&self) -> u64 {
+        self.max_weight.unwrap_or(u64::MAX)
+    }
+
+    pub fn burst(&self) -> usize {
+        self.burst.unwrap_or(1).max(1)
+    }
+
+    pub fn concurrent(&self) -> usize {
+        self.concurrent
+            .unwrap_or(Semaphore::MAX_PERMITS)
+            .clamp(1, Semaphore::MAX_PERMITS)
+    }
+}
+
+/// Frequency of batches, `count` batches per `period`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    pub count: u32,
+    pub period: Duration,
+}
+
+impl Rate {
+    pub fn per_second(count: u32) -> Self {
+        Self {
+            count,
+            period: Duration::from_secs(1),
+        }
+    }
+
+    /// Time between two batches once the burst is used up
+    pub fn interval(&self) -> Duration {
+        self.period / self.count.max(1)
+    }
+}
+
+/// Parses `<count>/<period>`, where the period is a unit ("10/s", "600/m") or a human readable
+/// duration ("5/100ms")
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(rate: &str) -> Result<Self, Self::Err> {
+        let (count, period) = batched_parse::parse_rate(rate)?;
+        Ok(Self { count, period })
+    }
+}
+
+/// Value accepted by the `rate` attribute when it isn't a literal
+pub trait IntoRate {
+    fn into_rate(self) -> Rate;
+}
+
+impl IntoRate for Rate {
+    fn into_rate(self) -> Rate {
+        self
+    }
+}
+
+impl IntoRate for &str {
+    fn into_rate(self) -> Rate {
+        self.parse()
+            .unwrap_or_else(|error| panic!("invalid batched rate {self:?}: {error}"))
+    }
+}
+
+impl IntoRate for String {
+    fn into_rate(self) -> Rate {
+        self.as_str().into_rate()
+    }
+}
+
+/// Window that adapts to the observed arrival rate of items and duration of the batched function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdaptiveWindow {
+    pub min: Duration,
+    pub max: Duration,
+    pub target: AdaptiveTarget,
+}
+
+/// What an [`AdaptiveWindow`] tunes the window for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdaptiveTarget {
+    /// Waits as long as batches take to reach this amount of items at the current arrival rate
+    BatchSize(usize),
+    /// Keeps the window plus the duration of the batched function around this latency
+    Latency(Duration),
+}
+
+impl AdaptiveWindow {
+    /// Window for an average `gap` between the arrival of items and average `duration` of the
+    /// batched function, both unknown until observed. The window falls back to `min` when no
+    /// further item is expected within it, so callers aren't delayed under low traffic
+    pub fn window(&self, gap: Option<Duration>, duration: Option<Duration>) -> Duration {
+        let window = match self.target {
+            AdaptiveTarget::BatchSize(size) => {
+                let remaining = u32::try_from(size.saturating_sub(1)).unwrap_or(u32::MAX);
+                let fill = gap.map(|gap| gap.saturating_mul(remaining));
+                fill.filter(|fill| *fill <= self.max).unwrap_or(self.min)
+            }
+            AdaptiveTarget::Latency(latency) => {
+                let window = latency.saturating_sub(duration.unwrap_or_default());
+                match gap {
+                    Some(gap) if gap <= window => window,
+                    _ => self.min,
+                }
+            }
+        };
+
+        window.clamp(self.min, self.max.max(self.min))
+    }
+}
+
+/// Shared, runtime-swappable [`BatchConfig`]. Clones share the same configuration
+#[derive(Debug, Clone)]
+pub struct BatchConfigCell {
+    inner: Arc<RwLock<Arc<BatchConfig>>>,
+}
+
+impl BatchConfigCell {
+    pub fn new(config: BatchConfig) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// Snapshot of the current configuration
+    pub fn load(&self) -> Arc<BatchConfig> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn store(&self, config: BatchConfig) {
+        *self.inner.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    }
+
+    /// Modifies a copy of the current configuration and stores it
+    pub fn update(&self, update: impl FnOnce(&mut BatchConfig)) {
+        let mut inner = self.inner.write().unwrap_or_else(|e| e.into_inner());
+        let mut config = BatchConfig::clone(&inner);
+        update(&mut config);
+        *inner = Arc::new(config);
+    }
+}
+
+/// Value accepted by the `window` and `windowN` attributes when it isn't a literal. Integers are
+/// interpreted as milliseconds and strings as human readable durations ("250ms", "1s")
+pub trait IntoDuration {
+    fn into_duration(self) -> Duration;
+}
+
+impl IntoDuration for Duration {
+    fn into_duration(self) -> Duration {
+        self
+    }
+}
+
+impl IntoDuration for &str {
+    fn into_duration(self) -> Duration {
+        humantime::parse_duration(self)
+            .unwrap_or_else(|error| panic!("invalid batched window {self:?}: {error}"))
+    }
+}
+
+impl IntoDuration for String {
+    fn into_duration(self) -> Duration {
+        self.as_str().into_duration()
+    }
+}
+
+macro_rules! impl_into_duration_millis {
+    ($($_type:ty),*) => {
+        $(
+            impl IntoDuration for $_type {
+                fn into_duration(self) -> Duration {
+                    let millis = u64::try_from(self).expect("batched window must not be negative");
+                    Duration::from_millis(millis)
+                }
+            }
+        )*
+    };
+}
+
+impl_into_duration_millis!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
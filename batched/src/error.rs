@@ -1,10 +1,46 @@
 use std::{
-    error::Error,
     fmt::{Debug, Display},
     ops::Deref,
     sync::Arc,
 };
 
+/// Failure of the batching itself rather than of the batched function. `fallible` batched
+/// functions return it (or fold it into their own error type), others panic with it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The executor was shut down (or its runtime is gone) before the call was processed
+    ExecutorClosed,
+    /// The batched function panicked while processing the batch of the call, with the panic
+    /// message
+    InnerPanicked(String),
+    /// The call did not complete within the configured timeout
+    Timeout,
+    /// The executor already holds the maximum amount of pending items
+    QueueFull,
+    /// The batch of the call was dropped before completing, e.g. by a runtime shutdown
+    Cancelled,
+    /// The batched function returned fewer results than it was given items
+    MissingResults { expected: usize, returned: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::ExecutorClosed => f.write_str("batched executor is closed"),
+            Error::InnerPanicked(message) => write!(f, "batched function panicked: {message}"),
+            Error::Timeout => f.write_str("batched call timed out"),
+            Error::QueueFull => f.write_str("batched queue is full"),
+            Error::Cancelled => f.write_str("batched call was cancelled"),
+            Error::MissingResults { expected, returned } => write!(
+                f,
+                "batched function returned {returned} results for {expected} items"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 pub struct SharedError<E> {
     inner: Arc<E>,
 }
@@ -35,7 +71,7 @@ impl<E> SharedError<E> {
     }
 }
 
-impl<E: Debug + Display> Error for SharedError<E> {}
+impl<E: Debug + Display> std::error::Error for SharedError<E> {}
 
 impl<E> From<E> for SharedError<E> {
     fn from(inner: E) -> Self {
@@ -1,7 +1,13 @@
-#![feature(try_trait_v2)]
-#![feature(negative_impls)]
-#![feature(specialization)]
-
+pub mod batcher;
+pub mod blocking;
+pub mod cache;
+pub mod config;
 pub mod error;
+mod metrics;
+pub mod retry;
+pub mod stats;
 pub use batched_derive::batched;
 pub mod tracing;
+pub use batcher::{Batcher, BatcherBuilder, BatcherMap, Priority, shutdown_all};
+pub use config::IntoDuration;
+pub use error::Error;
@@ -0,0 +1,51 @@
+//! Metrics emitted through the [`metrics`](https://docs.rs/metrics) facade when the `metrics`
+//! feature is enabled, every metric is labelled with the name of the batcher:
+//!
+//! - `batched_queue_depth` (gauge): items waiting for their batch to start
+//! - `batched_batches_total` (counter): batches, labelled with the `reason` the batch stopped
+//!   collecting calls (`limit`, `window`, `flush` or `shutdown`)
+//! - `batched_batch_size` (histogram): items per batch
+//! - `batched_window_seconds` (histogram): time between the first call of a batch and the end of
+//!   its window
+//! - `batched_duration_seconds` (histogram): time the batched function took to process a batch
+#![cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+
+use std::time::Instant;
+
+use crate::batcher::FlushReason;
+
+impl FlushReason {
+    #[cfg(feature = "metrics")]
+    fn as_str(self) -> &'static str {
+        match self {
+            FlushReason::Limit => "limit",
+            FlushReason::Window => "window",
+            FlushReason::Flush => "flush",
+            FlushReason::Shutdown => "shutdown",
+            FlushReason::Urgent => "urgent",
+        }
+    }
+}
+
+pub(crate) fn queue_depth(name: &str, depth: usize) {
+    #[cfg(feature = "metrics")]
+    ::metrics::gauge!("batched_queue_depth", "name" => name.to_string()).set(depth as f64);
+}
+
+pub(crate) fn batch_collected(name: &str, reason: FlushReason, size: usize, window_start: Instant) {
+    #[cfg(feature = "metrics")]
+    {
+        let name = name.to_string();
+        ::metrics::counter!("batched_batches_total", "name" => name.clone(), "reason" => reason.as_str())
+            .increment(1);
+        ::metrics::histogram!("batched_batch_size", "name" => name.clone()).record(size as f64);
+        ::metrics::histogram!("batched_window_seconds", "name" => name)
+            .record(window_start.elapsed());
+    }
+}
+
+pub(crate) fn batch_completed(name: &str, start: Instant) {
+    #[cfg(feature = "metrics")]
+    ::metrics::histogram!("batched_duration_seconds", "name" => name.to_string())
+        .record(start.elapsed());
+}
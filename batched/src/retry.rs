@@ -0,0 +1,95 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+type Predicate<E> = Arc<dyn Fn(&E) -> bool + Send + Sync>;
+
+/// How the delay between attempts grows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Every retry waits `base`
+    Constant,
+    /// The n-th retry waits `n * base`
+    Linear,
+    /// The n-th retry waits `2^(n - 1) * base`
+    Exponential,
+}
+
+/// Re-runs a whole batch while its result is an error, before the result is handed out to the
+/// callers of the batch
+pub struct Retry<E> {
+    attempts: u32,
+    backoff: Backoff,
+    base: Duration,
+    predicate: Option<Predicate<E>>,
+}
+
+impl<E> Clone for Retry<E> {
+    fn clone(&self) -> Self {
+        Self {
+            attempts: self.attempts,
+            backoff: self.backoff,
+            base: self.base,
+            predicate: self.predicate.clone(),
+        }
+    }
+}
+
+impl<E> Debug for Retry<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Retry")
+            .field("attempts", &self.attempts)
+            .field("backoff", &self.backoff)
+            .field("base", &self.base)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E> Retry<E> {
+    /// Runs a batch at most `attempts` times (including the first attempt), waiting 50ms with
+    /// exponential backoff between attempts
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            backoff: Backoff::Exponential,
+            base: Duration::from_millis(50),
+            predicate: None,
+        }
+    }
+
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Delay before the first retry
+    pub fn base(mut self, base: Duration) -> Self {
+        self.base = base;
+        self
+    }
+
+    /// Only retries errors for which `predicate` returns true (default: every error)
+    pub fn predicate(mut self, predicate: impl Fn(&E) -> bool + Send + Sync + 'static) -> Self {
+        self.predicate = Some(Arc::new(predicate));
+        self
+    }
+
+    /// Whether a batch that failed with `error` on attempt `attempt` (starting at 1) runs again
+    pub fn retries(&self, attempt: u32, error: &E) -> bool {
+        attempt < self.attempts
+            && self
+                .predicate
+                .as_ref()
+                .is_none_or(|predicate| predicate(error))
+    }
+
+    /// Delay after the failed attempt `attempt` (starting at 1)
+    pub fn delay(&self, attempt: u32) -> Duration {
+        match self.backoff {
+            Backoff::Constant => self.base,
+            Backoff::Linear => self.base.saturating_mul(attempt),
+            Backoff::Exponential => {
+                let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+                self.base.saturating_mul(factor)
+            }
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::batcher::FlushReason;
+
+/// Snapshot of the statistics of a batcher (or of every executor of a batched function)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchStats {
+    /// Items waiting for their batch to start
+    pub pending_items: usize,
+    /// Calls waiting for their batch to start
+    pub pending_calls: usize,
+    /// Batches currently running
+    pub inflight_batches: usize,
+    /// Batches started so far
+    pub batches: u64,
+    /// Items of the batches started so far
+    pub items: u64,
+    /// Why the batches started so far stopped collecting calls
+    pub flushes: FlushCounts,
+    /// Median amount of items per batch
+    pub batch_size_p50: u64,
+    pub batch_size_p99: u64,
+    /// Median time between the first call of a batch and the start of the batch (window and
+    /// concurrency limit)
+    pub wait_p50: Duration,
+    pub wait_p99: Duration,
+    batch_sizes: Vec<u64>,
+    waits: Vec<u64>,
+}
+
+impl BatchStats {
+    /// Adds the statistics of another batcher
+    pub(crate) fn merge(&mut self, other: &BatchStats) {
+        self.pending_items += other.pending_items;
+        self.pending_calls += other.pending_calls;
+        self.inflight_batches += other.inflight_batches;
+        self.batches += other.batches;
+        self.items += other.items;
+        self.flushes.limit += other.flushes.limit;
+        self.flushes.window += other.flushes.window;
+        self.flushes.flush += other.flushes.flush;
+        self.flushes.shutdown += other.flushes.shutdown;
+        self.flushes.urgent += other.flushes.urgent;
+
+        merge_buckets(&mut self.batch_sizes, &other.batch_sizes);
+        merge_buckets(&mut self.waits, &other.waits);
+        self.compute_percentiles();
+    }
+
+    fn compute_percentiles(&mut self) {
+        self.batch_size_p50 = percentile(&self.batch_sizes, 0.5);
+        self.batch_size_p99 = percentile(&self.batch_sizes, 0.99);
+        self.wait_p50 = Duration::from_micros(percentile(&self.waits, 0.5));
+        self.wait_p99 = Duration::from_micros(percentile(&self.waits, 0.99));
+    }
+}
+
+/// Amount of batches per reason they stopped collecting calls
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlushCounts {
+    /// The batch reached the size limit
+    pub limit: u64,
+    /// The window of the batch elapsed
+    pub window: u64,
+    /// The batcher was flushed
+    pub flush: u64,
+    /// The batcher was shut down
+    pub shutdown: u64,
+    /// An urgent call joined the batch
+    pub urgent: u64,
+}
+
+/// Counters of a batcher, updated by its callers, collector and batch tasks
+#[derive(Default)]
+pub(crate) struct Stats {
+    pub(crate) pending_calls: AtomicUsize,
+    pub(crate) inflight_batches: AtomicUsize,
+    batches: AtomicU64,
+    items: AtomicU64,
+    flushes: [AtomicU64; 5],
+    batch_sizes: Histogram,
+    waits: Histogram,
+}
+
+impl Stats {
+    pub(crate) fn batch_started(&self, reason: FlushReason, size: usize, wait: Duration) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.items.fetch_add(size as u64, Ordering::Relaxed);
+        self.flushes[reason as usize].fetch_add(1, Ordering::Relaxed);
+        self.batch_sizes.record(size as u64);
+        self.waits.record(wait.as_micros() as u64);
+    }
+
+    pub(crate) fn snapshot(&self, pending_items: usize) -> BatchStats {
+        let flush = |reason: FlushReason| self.flushes[reason as usize].load(Ordering::Relaxed);
+        let mut stats = BatchStats {
+            pending_items,
+            pending_calls: self.pending_calls.load(Ordering::Relaxed),
+            inflight_batches: self.inflight_batches.load(Ordering::Relaxed),
+            batches: self.batches.load(Ordering::Relaxed),
+            items: self.items.load(Ordering::Relaxed),
+            flushes: FlushCounts {
+                limit: flush(FlushReason::Limit),
+                window: flush(FlushReason::Window),
+                flush: flush(FlushReason::Flush),
+                shutdown: flush(FlushReason::Shutdown),
+                urgent: flush(FlushReason::Urgent),
+            },
+            batch_sizes: self.batch_sizes.buckets(),
+            waits: self.waits.buckets(),
+            ..Default::default()
+        };
+        stats.compute_percentiles();
+        stats
+    }
+}
+
+/// Sub-buckets per power of two, values are kept with a precision of 1/8 (12.5%)
+const SUB_BUCKETS: usize = 8;
+const BUCKETS: usize = SUB_BUCKETS * 62;
+
+/// Histogram of `u64` values on log-linear buckets, recorded to without locking
+struct Histogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+}
+
+impl Histogram {
+    fn record(&self, value: u64) {
+        self.buckets[bucket(value)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn buckets(&self) -> Vec<u64> {
+        let buckets = self.buckets.iter();
+        buckets
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect()
+    }
+}
+
+fn bucket(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+
+    let exponent = 63 - value.leading_zeros() as usize;
+    let mantissa = (value >> (exponent - 3)) as usize & (SUB_BUCKETS - 1);
+    (exponent - 2) * SUB_BUCKETS + mantissa
+}
+
+/// Smallest value of a bucket
+fn bucket_value(bucket: usize) -> u64 {
+    if bucket < SUB_BUCKETS {
+        return bucket as u64;
+    }
+
+    let exponent = bucket / SUB_BUCKETS + 2;
+    let mantissa = (bucket % SUB_BUCKETS) as u64;
+    (SUB_BUCKETS as u64 + mantissa) << (exponent - 3)
+}
+
+fn percentile(buckets: &[u64], percentile: f64) -> u64 {
+    let total: u64 = buckets.iter().sum();
+    let rank = ((total as f64 * percentile).ceil() as u64).max(1);
+
+    let mut seen = 0;
+    for (bucket, count) in buckets.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return bucket_value(bucket);
+        }
+    }
+    0
+}
+
+fn merge_buckets(buckets: &mut Vec<u64>, other: &[u64]) {
+    buckets.resize(buckets.len().max(other.len()), 0);
+    for (bucket, count) in buckets.iter_mut().zip(other) {
+        *bucket += count;
+    }
+}
@@ -1,11 +1,14 @@
 pub use tracing::*;
 
+/// Links the span of a batch to the span of every call it processes. Linking is done by
+/// OpenTelemetry, so without the `tracing_opentelemetry` feature spans aren't linked
 pub trait TracingSpan {
     fn link_span(&mut self, span: &Span);
 }
 
-impl<N> TracingSpan for N {
-    default fn link_span(&mut self, _span: &Span) {}
+#[cfg(not(feature = "tracing_opentelemetry"))]
+impl TracingSpan for Span {
+    fn link_span(&mut self, _span: &Span) {}
 }
 
 #[cfg(feature = "tracing_opentelemetry")]
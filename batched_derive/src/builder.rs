@@ -1,7 +1,5 @@
-use std::usize;
-
 use proc_macro2::TokenStream;
-use quote::{format_ident, quote};
+use quote::{ToTokens, format_ident, quote};
 use syn::Ident;
 
 use crate::parse::{Attributes, Function, FunctionResultType};
@@ -9,10 +7,24 @@ use crate::parse::{Attributes, Function, FunctionResultType};
 struct Identifiers {
     public_interface: Ident,
     public_interface_multiple: Ident,
+    public_interface_urgent: Ident,
+    public_interface_with_priority: Ident,
+    public_interface_blocking: Ident,
+    public_interface_multiple_blocking: Ident,
     inner_batched: Ident,
     inner_passthrough: Ident,
     executor_producer_channel: Ident,
     executor_background_fn: Ident,
+    executor_lookup_fn: Ident,
+    executor_config: Ident,
+    executor_registry_fn: Ident,
+    config_fn: Ident,
+    flush_fn: Ident,
+    shutdown_fn: Ident,
+    stats_fn: Ident,
+    cache_fn: Ident,
+    invalidate_fn: Ident,
+    clear_fn: Ident,
 }
 
 fn build_identifiers(call_function: &Function) -> Identifiers {
@@ -20,19 +32,47 @@ fn build_identifiers(call_function: &Function) -> Identifiers {
 
     let public_interface = format_ident!("{id}");
     let public_interface_multiple = format_ident!("{id}_multiple");
+    let public_interface_urgent = format_ident!("{id}_urgent");
+    let public_interface_with_priority = format_ident!("{id}_with_priority");
+    let public_interface_blocking = format_ident!("{id}_blocking");
+    let public_interface_multiple_blocking = format_ident!("{id}_multiple_blocking");
     let inner_batched = format_ident!("{id}__batched");
     let inner_passthrough = format_ident!("{id}__passthrough");
 
     let executor_producer_channel = format_ident!("BATCHED_{}", id.to_uppercase());
     let executor_background_fn = format_ident!("spawn_executor_{id}");
+    let executor_lookup_fn = format_ident!("executor_{id}");
+    let executor_config = format_ident!("BATCHED_{}_CONFIG", id.to_uppercase());
+    let executor_registry_fn = format_ident!("executors_{id}");
+    let config_fn = format_ident!("{id}_config");
+    let flush_fn = format_ident!("flush_{id}");
+    let shutdown_fn = format_ident!("shutdown_{id}");
+    let stats_fn = format_ident!("{id}_stats");
+    let cache_fn = format_ident!("cache_{id}");
+    let invalidate_fn = format_ident!("invalidate_{id}");
+    let clear_fn = format_ident!("clear_{id}");
 
     Identifiers {
         public_interface,
         public_interface_multiple,
+        public_interface_urgent,
+        public_interface_with_priority,
+        public_interface_blocking,
+        public_interface_multiple_blocking,
         inner_batched,
         inner_passthrough,
         executor_producer_channel,
         executor_background_fn,
+        executor_lookup_fn,
+        executor_config,
+        executor_registry_fn,
+        config_fn,
+        flush_fn,
+        shutdown_fn,
+        stats_fn,
+        cache_fn,
+        invalidate_fn,
+        clear_fn,
     }
 }
 
@@ -60,9 +100,9 @@ fn build_public_interface(
     let inner_body = &call_function.inner;
     let returned = &call_function.returned.tokens;
 
-    let (is_result, is_vec) = function_flags(&call_function);
     let asynchronous = options.asynchronous;
     let passthrough = options.passthrough;
+    let fallible = options.fallible;
 
     let return_type = match &call_function.returned.result_type {
         FunctionResultType::Raw(token) => token.clone(),
@@ -75,29 +115,6 @@ fn build_public_interface(
             }
         }
     };
-    let return_result = if is_result {
-        if is_vec {
-            quote! {
-                let mut result = result?;
-                let result = result.remove(0);
-                Ok(result)
-            }
-        } else {
-            quote! {
-                let result = result?;
-                Ok(result)
-            }
-        }
-    } else if is_vec {
-        quote! {
-            let result = result.remove(0);
-            result
-        }
-    } else {
-        quote! {
-            result
-        }
-    };
 
     let return_type_multiple = match &call_function.returned.result_type {
         FunctionResultType::Raw(token) => token.clone(),
@@ -110,14 +127,6 @@ fn build_public_interface(
             }
         }
     };
-    let return_result_multiple = if is_result {
-        quote! {
-            let result = result?;
-            Ok(result)
-        }
-    } else {
-        quote! { result }
-    };
 
     let cast_result_error = match &call_function.returned.result_type {
         FunctionResultType::Raw(_) => None,
@@ -127,21 +136,140 @@ fn build_public_interface(
             }),
     };
 
-    let executor_producer_channel = &identifiers.executor_producer_channel;
-    let executor_background_fn = &identifiers.executor_background_fn;
+    let executor_lookup_fn = &identifiers.executor_lookup_fn;
     let inner_batched = &identifiers.inner_batched;
     let inner_passthrough = &identifiers.inner_passthrough;
     let public_interface = &identifiers.public_interface;
     let public_interface_multiple = &identifiers.public_interface_multiple;
+    let public_interface_urgent = &identifiers.public_interface_urgent;
+    let public_interface_with_priority = &identifiers.public_interface_with_priority;
+    let public_interface_blocking = &identifiers.public_interface_blocking;
+    let public_interface_multiple_blocking = &identifiers.public_interface_multiple_blocking;
+
+    let key_arg = call_function.key_arg.as_ref();
+    let key_name: Option<TokenStream> = key_arg.map(|key_arg| syn::parse_str(&key_arg.name).unwrap());
+    let key_type = key_arg.map(|key_arg| &key_arg._type);
+    let inner_args = with_key(call_function, key_arg.map(|key_arg| &key_arg.arg), arg);
+    let public_args = with_key(call_function, quote! { #key_name: #key_type }, quote! { #arg_name: #arg_type });
+    let public_args_multiple = with_key(call_function, quote! { #key_name: #key_type }, quote! { #arg_name: Vec<#arg_type> });
+    let lookup_args = key_name.as_ref().map(|key_name| quote! { &#key_name });
 
     #[cfg(feature = "tracing_span")]
     let tracing_span = quote! { #[tracing::instrument(skip_all)] };
     #[cfg(not(feature = "tracing_span"))]
     let tracing_span = quote! {};
 
+    let receiver = call_function.receiver.as_ref().map(|receiver| quote! { #receiver, });
+    let executor = if receiver.is_some() {
+        quote! { self.#executor_lookup_fn(#lookup_args) }
+    } else {
+        quote! { #executor_lookup_fn(#lookup_args) }
+    };
+
+    // `<name>_urgent` and `<name>_with_priority`, `call` being the executor call for a priority
+    let priority_interface = |call: &dyn Fn(TokenStream) -> TokenStream, return_type: &TokenStream| {
+        let urgent_call = call(quote! { ::batched::Priority::Urgent });
+        let priority_call = call(quote! { priority });
+        quote! {
+            #tracing_span
+            #visibility async fn #public_interface_urgent(#receiver #public_args) -> #return_type {
+                #urgent_call
+            }
+
+            #tracing_span
+            #visibility async fn #public_interface_with_priority(#receiver #public_args, priority: ::batched::Priority) -> #return_type {
+                #priority_call
+            }
+        }
+    };
+
+    // `<name>_blocking` and `<name>_multiple_blocking`, for synchronous callers
+    let call_args = with_key(call_function, &key_name, &arg_name);
+    let call_target = receiver.as_ref().map(|_| quote! { self. });
+    let blocking_interface = |return_type: &TokenStream, return_type_multiple: &TokenStream| quote! {
+        #visibility fn #public_interface_blocking(#receiver #public_args) -> #return_type {
+            ::batched::blocking::block_on(#call_target #public_interface(#call_args))
+        }
+
+        #visibility fn #public_interface_multiple_blocking(#receiver #public_args_multiple) -> #return_type_multiple {
+            ::batched::blocking::block_on(#call_target #public_interface_multiple(#call_args))
+        }
+    };
+
+    // With `cache`, calls go through the cache first and only misses reach the executor
+    let cache = options.cache.as_ref().map(|cache| {
+        let value_type = match &call_function.returned.result_type {
+            FunctionResultType::VectorRaw(value_type) => value_type,
+            FunctionResultType::Result(output, _, _) => match &output.result_type {
+                FunctionResultType::VectorRaw(value_type) => value_type,
+                _ => unreachable!("validated by Attributes::validate"),
+            },
+            FunctionResultType::Raw(_) => unreachable!("validated by Attributes::validate"),
+        };
+        let (key_type, item_key, call_key) = match (&key_name, key_type) {
+            (Some(key_name), Some(key_type)) => (
+                quote! { (#key_type, #arg_type) },
+                quote! { (#key_name.clone(), item.clone()) },
+                quote! { (#key_name.clone(), #arg_name.clone()) },
+            ),
+            _ => (quote! { #arg_type }, quote! { item.clone() }, quote! { #arg_name.clone() }),
+        };
+
+        let try_ = fallible || matches!(call_function.returned.result_type, FunctionResultType::Result(..));
+        let (get_or_load, get_or_load_many) = match try_ {
+            true => (quote! { try_get_or_load }, quote! { try_get_or_load_many }),
+            false => (quote! { get_or_load }, quote! { get_or_load_many }),
+        };
+
+        let ttl = &cache.ttl;
+        let capacity = cache.capacity.clone().unwrap_or(quote! { 10_000 });
+        let cache_fn = &identifiers.cache_fn;
+        let invalidate_fn = &identifiers.invalidate_fn;
+        let clear_fn = &identifiers.clear_fn;
+        let cache_type = quote! { ::batched::cache::Cache<#key_type, #value_type> };
+
+        let interface = quote! {
+            #[doc(hidden)]
+            fn #cache_fn() -> &'static #cache_type {
+                static CACHE: ::std::sync::LazyLock<#cache_type> =
+                    ::std::sync::LazyLock::new(|| ::batched::cache::Cache::new(#ttl, #capacity));
+                &CACHE
+            }
+
+            /// Removes the cached result of a call
+            #visibility fn #invalidate_fn(#public_args) {
+                #cache_fn().invalidate(&#call_key);
+            }
+
+            /// Removes every cached result
+            #visibility fn #clear_fn() {
+                #cache_fn().clear();
+            }
+        };
+        let cached = move |call: TokenStream| quote! {
+            #cache_fn().#get_or_load(#call_key, async { #call }).await
+        };
+        (interface, cached, item_key, cache_fn.clone(), get_or_load_many)
+    });
+    let cached = |call: TokenStream| match &cache {
+        Some((_, cached, ..)) => cached(call),
+        None => call,
+    };
+    // `fold` hands out the batching errors of the cache like those of the executor
+    let cached_many = |call: TokenStream, fold: TokenStream| match &cache {
+        Some((_, _, item_key, cache_fn, get_or_load_many)) => quote! {
+            #cache_fn()
+                .#get_or_load_many(#arg_name, |item: &#arg_type| #item_key, async |#arg_name| #call)
+                .await
+                #fold
+        },
+        None => call,
+    };
+    let cache_interface = cache.as_ref().map(|(interface, ..)| interface);
+
     let inner_batched = quote! {
         #(#macros)*
-        async fn #inner_batched(#arg) -> #returned {
+        async fn #inner_batched(#receiver #inner_args) -> #returned {
             let result = async { #inner_body };
             let result = result.await;
             #cast_result_error
@@ -150,7 +278,7 @@ fn build_public_interface(
     };
     let passthrough = if passthrough { quote! {
         #(#macros)*
-        #visibility async fn #inner_passthrough(#arg) -> #returned {
+        #visibility async fn #inner_passthrough(#receiver #inner_args) -> #returned {
             let result = async { #inner_body };
             let result = result.await;
             #cast_result_error
@@ -158,54 +286,134 @@ fn build_public_interface(
         }
     } } else { quote! {} };
 
-    if asynchronous {
+    if asynchronous && fallible {
+        let priority_interface = priority_interface(
+            &|priority| quote! { #executor.try_fire_and_forget_with_priority(vec![#arg_name], #priority).await },
+            &quote! { Result<(), ::batched::Error> },
+        );
+        let return_type = quote! { Result<(), ::batched::Error> };
+        let blocking_interface = blocking_interface(&return_type, &return_type);
+        quote! {
+            #inner_batched
+            #passthrough
+
+            #tracing_span
+            #visibility async fn #public_interface(#receiver #public_args) -> Result<(), ::batched::Error> {
+                #executor.try_fire_and_forget(vec![#arg_name]).await
+            }
+
+            #tracing_span
+            #visibility async fn #public_interface_multiple(#receiver #public_args_multiple) -> Result<(), ::batched::Error> {
+                #executor.try_fire_and_forget(#arg_name).await
+            }
+
+            #priority_interface
+            #blocking_interface
+        }
+    } else if asynchronous {
+        let priority_interface = priority_interface(
+            &|priority| quote! { #executor.fire_and_forget_with_priority(vec![#arg_name], #priority).await; },
+            &quote! { () },
+        );
+        let blocking_interface = blocking_interface(&quote! { () }, &quote! { () });
         quote! {
             #inner_batched
             #passthrough
 
             #tracing_span
-            #visibility async fn #public_interface(#arg_name: #arg_type) {
-                #public_interface_multiple(vec![#arg_name]).await;
+            #visibility async fn #public_interface(#receiver #public_args) {
+                #executor.fire_and_forget(vec![#arg_name]).await;
             }
 
             #tracing_span
-            #visibility async fn #public_interface_multiple(#arg_name: Vec<#arg_type>) {
-                let channel = &#executor_producer_channel;
-                let channel = channel.get_or_init(async || { #executor_background_fn().await }).await;
+            #visibility async fn #public_interface_multiple(#receiver #public_args_multiple) {
+                #executor.fire_and_forget(#arg_name).await;
+            }
+
+            #priority_interface
+            #blocking_interface
+        }
+    } else if fallible {
+        // Batching errors are folded into the error type of the function if it returns a
+        // `Result`, otherwise the function returns `Result<_, batched::Error>`
+        let (return_type, return_type_multiple, fold_error) = match &call_function.returned.result_type {
+            FunctionResultType::Result(_, error, inner_shared_error) => {
+                let fold_error = match inner_shared_error {
+                    Some(inner) => quote! {
+                        <#error as ::std::convert::From<#inner>>::from(
+                            <#inner as ::std::convert::From<::batched::Error>>::from(error)
+                        )
+                    },
+                    None => quote! { <#error as ::std::convert::From<::batched::Error>>::from(error) },
+                };
+                (return_type, return_type_multiple, Some(quote! { .unwrap_or_else(|error| Err(#fold_error)) }))
+            }
+            _ => (
+                quote! { Result<#return_type, ::batched::Error> },
+                quote! { Result<#return_type_multiple, ::batched::Error> },
+                None,
+            ),
+        };
+
+        let priority_interface = priority_interface(
+            &|priority| cached(quote! { #executor.try_call_with_priority(#arg_name, #priority).await #fold_error }),
+            &return_type,
+        );
+        let call = cached(quote! { #executor.try_call(#arg_name).await #fold_error });
+        let fold_cache_error = fold_error.clone().unwrap_or(quote! { .unwrap_or_else(Err) });
+        let call_many = cached_many(quote! { #executor.try_call_many(#arg_name).await #fold_error }, fold_cache_error);
+        let blocking_interface = blocking_interface(&return_type, &return_type_multiple);
+
+        quote! {
+            #inner_batched
+            #passthrough
+
+            #tracing_span
+            #visibility async fn #public_interface(#receiver #public_args) -> #return_type {
+                #call
+            }
 
-                let span = ::batched::tracing::Span::current();
-                channel.send((#arg_name, span, None)).await
-                    .expect("batched function panicked (send)");
+            #tracing_span
+            #visibility async fn #public_interface_multiple(#receiver #public_args_multiple) -> #return_type_multiple {
+                #call_many
             }
+
+            #priority_interface
+            #blocking_interface
+            #cache_interface
         }
     } else {
+        let priority_interface = priority_interface(
+            &|priority| cached(quote! { #executor.call_with_priority(#arg_name, #priority).await }),
+            &return_type,
+        );
+        let call = cached(quote! { #executor.call(#arg_name).await });
+        let call_many = cached_many(
+            quote! { #executor.call_many(#arg_name).await },
+            quote! { .unwrap_or_else(|error| panic!("{error}")) },
+        );
+        let blocking_interface = blocking_interface(&return_type, &return_type_multiple);
+
         quote! {
             #inner_batched
             #passthrough
-            
+
             #tracing_span
-            #visibility async fn #public_interface(#arg_name: #arg_type) -> #return_type {
-                let mut result = #public_interface_multiple(vec![#arg_name]).await;
-                #return_result
+            #visibility async fn #public_interface(#receiver #public_args) -> #return_type {
+                #call
             }
 
             #tracing_span
-            #visibility async fn #public_interface_multiple(#arg_name: Vec<#arg_type>) -> #return_type_multiple {
-                let channel = &#executor_producer_channel;
-                let channel = channel.get_or_init(async || { #executor_background_fn().await }).await;
-
-                let (response_channel_sender, mut response_channel_recv) = ::tokio::sync::mpsc::channel(1);
-                let span = ::batched::tracing::Span::current();
-                channel.send((#arg_name, span, Some(response_channel_sender))).await
-                    .expect("batched function panicked (send)");
-
-                let result = response_channel_recv.recv().await
-                    .expect("batched function panicked (recv)");
-                #return_result_multiple
+            #visibility async fn #public_interface_multiple(#receiver #public_args_multiple) -> #return_type_multiple {
+                #call_many
             }
+
+            #priority_interface
+            #blocking_interface
+            #cache_interface
         }
     }
-    
+
 }
 
 fn build_executor(
@@ -213,158 +421,259 @@ fn build_executor(
     call_function: &Function,
     options: &Attributes,
 ) -> TokenStream {
-    const SEMAPHORE_MAX_PERMITS: usize = 2305843009213693951;
-
-    let capacity = options.limit.unwrap_or(usize::MAX);
-    let concurrent_limit = options.concurrent_limit.unwrap_or(SEMAPHORE_MAX_PERMITS);
-    let default_window = options.default_window;
+    let default_window = &options.default_window;
+    let limit = options.limit.as_ref().map(|limit| quote! { Some(#limit) });
+    let limit = limit.unwrap_or(quote! { None });
+    let concurrent_limit = options.concurrent_limit.as_ref().map(|limit| quote! { Some(#limit) });
+    let concurrent_limit = concurrent_limit.unwrap_or(quote! { None });
     let asynchronous = options.asynchronous;
-    
+    let cancel_on_drop = options.cancel_on_drop;
+    let timeout = options.timeout.as_ref().map(|timeout| quote! { Some(#timeout) });
+    let timeout = timeout.unwrap_or(quote! { None });
+    let queue = options.queue.as_ref().map(|queue| quote! { Some(#queue) });
+    let queue = queue.unwrap_or(quote! { None });
+    let rate = options.rate.as_ref().map(|rate| quote! { Some(#rate) });
+    let rate = rate.unwrap_or(quote! { None });
+    let burst = options.burst.as_ref().map(|burst| quote! { Some(#burst) });
+    let burst = burst.unwrap_or(quote! { None });
+    let min = options.min.as_ref().map(|min| quote! { Some(#min) });
+    let min = min.unwrap_or(quote! { None });
+    let max_wait = options.max_wait.as_ref().map(|max_wait| quote! { Some(#max_wait) });
+    let max_wait = max_wait.unwrap_or(quote! { None });
+    let max_weight = options.max_weight.as_ref().map(|max| quote! { Some(#max) });
+    let max_weight = max_weight.unwrap_or(quote! { None });
+    let adaptive = options.adaptive.as_ref().map(|adaptive| quote! { Some(#adaptive) });
+    let adaptive = adaptive.unwrap_or(quote! { None });
+
     let windows = options.windows.iter();
     let windows = windows.map(|(call_size, call_window)| {
-        quote! { windows.insert(#call_size, #call_window); }
+        quote! { config.windows.insert(#call_size, #call_window); }
     });
-    let windows = quote! {
-        let mut windows = ::std::collections::BTreeMap::<u64, u64>::new();
-        #(#windows)*
-    };
 
     let arg_type = &call_function.batched_arg_type;
-    let returned_type_plural = match &call_function.returned.result_type {
-        FunctionResultType::Raw(token) => token.clone(),
-        FunctionResultType::VectorRaw(token) => quote! { Vec<#token> },
-        FunctionResultType::Result(output, error, _) => {
-            let tokens = &output.tokens;
-            match &output.result_type {
-                FunctionResultType::VectorRaw(token) => quote! { Result<Vec<#token>, #error> },
-                _ => quote! { Result<#tokens, #error> },
-            }
-        }
+    let returned = &call_function.returned.tokens;
+    let (_, is_vec) = function_flags(call_function);
+
+    // Results are only split per caller if someone is waiting for them
+    let (builder, fanout) = if is_vec && !asynchronous {
+        (quote! { builder_split }, quote! { ::batched::batcher::Split })
+    } else {
+        (quote! { builder }, quote! { ::batched::batcher::Cloned })
     };
+    let batcher_type = quote! { ::batched::Batcher<#arg_type, #returned, #fanout> };
 
-    let (is_result, is_vec) = function_flags(call_function);
+    let inner_batched = &identifiers.inner_batched;
+    let batched_span_name = inner_batched.to_string();
+    let executor_producer_channel = &identifiers.executor_producer_channel;
+    let executor_background_fn = &identifiers.executor_background_fn;
+    let executor_lookup_fn = &identifiers.executor_lookup_fn;
+    let executor_config = &identifiers.executor_config;
+    let executor_registry_fn = &identifiers.executor_registry_fn;
+    let config_fn = &identifiers.config_fn;
+    let flush_fn = &identifiers.flush_fn;
+    let shutdown_fn = &identifiers.shutdown_fn;
+    let stats_fn = &identifiers.stats_fn;
+
+    let is_method = call_function.receiver.is_some();
+    let config_call = if is_method {
+        quote! { Self::#config_fn() }
+    } else {
+        quote! { #config_fn() }
+    };
 
-    let handle_result = if is_result {
-        if is_vec {
-            quote! {
-                let result = result.as_mut().map(|r| r.drain(..count).collect()).map_err(|e| e.clone());
-            }
-        } else {
-            quote! {
-                let result = result.clone();
-            }
-        }
-    } else if is_vec {
-        quote! {
-            let result = result.drain(..count).collect();
+    let visibility = &call_function.visibility;
+    let config = quote! {
+        /// Batching configuration of the executor, changes apply from the next batch
+        #visibility fn #config_fn() -> &'static ::batched::config::BatchConfigCell {
+            static #executor_config: ::std::sync::LazyLock<::batched::config::BatchConfigCell> =
+                ::std::sync::LazyLock::new(|| {
+                    let mut config = ::batched::config::BatchConfig::new(#default_window);
+                    config.limit = #limit;
+                    config.min = #min;
+                    config.max_wait = #max_wait;
+                    config.max_weight = #max_weight;
+                    config.concurrent = #concurrent_limit;
+                    config.rate = #rate;
+                    config.burst = #burst;
+                    config.cancel_on_drop = #cancel_on_drop;
+                    config.timeout = #timeout;
+                    config.queue = #queue;
+                    config.adaptive = #adaptive;
+                    #(#windows)*
+                    ::batched::config::BatchConfigCell::new(config)
+                });
+            &#executor_config
         }
+    };
+
+    let key_arg = call_function.key_arg.as_ref();
+    let key_type = key_arg.map(|key_arg| &key_arg._type);
+
+    let mut instance = quote! {};
+    let mut instance_clone = quote! {};
+    if is_method {
+        instance.extend(quote! { let this = self.clone(); });
+        instance_clone.extend(quote! { let this = this.clone(); });
+    }
+    if key_arg.is_some() {
+        instance_clone.extend(quote! { let key = key.clone(); });
+    }
+
+    let inner_args = with_key(call_function, quote! { key }, quote! { data });
+    let inner_call = if is_method {
+        quote! { this.#inner_batched(#inner_args) }
     } else {
+        quote! { #inner_batched(#inner_args) }
+    };
+
+    let receiver = is_method.then(|| quote! { &self, });
+    let key_param = key_type.map(|key_type| quote! { key: #key_type });
+
+    let retry = options.retry.as_ref().map(|retry| {
+        let attempts = retry.attempts.clone().unwrap_or(quote! { 3 });
+        let backoff = retry.backoff.as_ref().map(|backoff| quote! { .backoff(#backoff) });
+        let base = retry.base.as_ref().map(|base| quote! { .base(#base) });
+        // Called rather than passed, so predicates taking `&E` accept `&SharedError<E>` as well
+        let predicate = retry.predicate.as_ref().map(|predicate| quote! {
+            .predicate(|error| #predicate(error))
+        });
+        let error = match &call_function.returned.result_type {
+            FunctionResultType::Result(_, error, _) => error,
+            _ => unreachable!("validated by Attributes::validate"),
+        };
         quote! {
-            let result = result.clone();
+            .retry(::batched::retry::Retry::<#error>::new(#attempts) #backoff #base #predicate)
+        }
+    });
+
+    let split_on_error = options.split_on_error.as_ref().map(|_| quote! { .split_on_error() });
+    let dedupe = options.dedupe.then(|| quote! { .dedupe() });
+    let weight = options.weight.as_ref().map(|(_, weight)| quote! {
+        .weight(|item: &#arg_type| #weight(item))
+    });
+
+    let executor = quote! {
+        #config
+
+        #[doc(hidden)]
+        fn #executor_background_fn(#receiver #key_param) -> #batcher_type {
+            #instance
+            ::batched::Batcher::#builder(move |data: Vec<#arg_type>| {
+                #instance_clone
+                async move { #inner_call.await }
+            })
+            .name(#batched_span_name)
+            .span(|count| ::batched::tracing::info_span!(#batched_span_name, count))
+            .config(#config_call.clone())
+            #weight
+            #dedupe
+            #retry
+            #split_on_error
+            .build()
         }
     };
 
-    let channel_type = quote! { (Vec<#arg_type>, ::batched::tracing::Span, Option<::tokio::sync::mpsc::Sender<#returned_type_plural>>) };
-    let propagate_result = if asynchronous { quote! {} } else {
-        quote! {
-            for (channel, count) in channels {
-                #handle_result
-                if let Some(channel) = channel {
-                    let _ = channel.try_send(result);
-                }
+    if !is_method && key_arg.is_none() {
+        return quote! {
+            #executor
+
+            #[doc(hidden)]
+            fn #executor_lookup_fn() -> #batcher_type {
+                static #executor_producer_channel: ::std::sync::LazyLock<#batcher_type> =
+                    ::std::sync::LazyLock::new(#executor_background_fn);
+                #executor_producer_channel.clone()
             }
-        }
+
+            /// Processes pending calls right away and waits for running batches
+            #visibility async fn #flush_fn() {
+                #executor_lookup_fn().flush().await
+            }
+
+            /// Processes pending calls and waits for running batches, calls made afterwards are
+            /// rejected
+            #visibility async fn #shutdown_fn() {
+                #executor_lookup_fn().shutdown().await
+            }
+
+            /// Snapshot of the statistics of the executor
+            #visibility fn #stats_fn() -> ::batched::stats::BatchStats {
+                #executor_lookup_fn().stats()
+            }
+        };
+    }
+
+    // Every instance (for methods) and partition key gets its own executor. Statics can't be
+    // declared inside impl blocks (or refer to `Self`), so executors live in a registry local to
+    // a function
+    let instance = options.instance.as_ref();
+    let instance_key = quote! { ::batched::batcher::InstanceKey::new(#instance) };
+    let instance_key_type = quote! { ::batched::batcher::InstanceKey };
+
+    let (registry_key_type, registry_key) = match (is_method, key_type) {
+        (true, Some(key_type)) => (quote! { (#instance_key_type, #key_type) }, quote! { (#instance_key, key.clone()) }),
+        (true, None) => (instance_key_type, instance_key),
+        (false, Some(key_type)) => (quote! { #key_type }, quote! { key.clone() }),
+        (false, None) => unreachable!(),
     };
 
-    let inner_batched = &identifiers.inner_batched;
-    let batched_span_name = inner_batched.to_string();
-    let executor_producer_channel = &identifiers.executor_producer_channel;
-    let executor_background_fn = &identifiers.executor_background_fn;
+    let key_ref_param = key_type.map(|key_type| quote! { key: &#key_type });
+    let spawn_key = key_type.map(|_| quote! { key.clone() });
+    let spawn_executor = if is_method {
+        quote! { self.#executor_background_fn(#spawn_key) }
+    } else {
+        quote! { #executor_background_fn(#spawn_key) }
+    };
+
+    let registry_type = quote! {
+        ::batched::BatcherMap<#registry_key_type, #arg_type, #returned, #fanout>
+    };
+    let registry_call = if is_method {
+        quote! { Self::#executor_registry_fn() }
+    } else {
+        quote! { #executor_registry_fn() }
+    };
 
     quote! {
-        static #executor_producer_channel:
-            ::tokio::sync::OnceCell<::tokio::sync::mpsc::Sender<#channel_type>> = ::tokio::sync::OnceCell::const_new();
-
-        async fn #executor_background_fn() -> ::tokio::sync::mpsc::Sender<#channel_type> {
-            let capacity = #capacity;
-            let default_window = #default_window;
-            #windows
-
-
-            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
-            tokio::task::spawn(async move {
-                let semaphore = ::std::sync::Arc::new(::tokio::sync::Semaphore::new(#concurrent_limit));
-
-                loop {
-                    let mut data_buffer = Vec::new();
-                    let mut return_channels: Vec<(Option<::tokio::sync::mpsc::Sender<#returned_type_plural>>, usize)> = vec![];
-                    let mut waiting_spans: Vec<::batched::tracing::Span> = vec![];
-
-                    let window_start = ::std::time::Instant::now();
-
-                    loop {
-                        let window = windows.iter()
-                            .find(|(max_calls, _)|  **max_calls >= data_buffer.len() as u64)
-                            .map(|(_, window)| window);
-                        let window = window.unwrap_or(&default_window);
-                        let window = ::std::time::Duration::from_millis(*window as u64);
-
-                        let window_end = window_start + window;
-                        let remaining_duration = window_end.duration_since(std::time::Instant::now());
-
-                        tokio::select! {
-                            event = receiver.recv() => {
-                                if event.is_none() {
-                                    return;
-                                }
-
-                                let event: #channel_type = event.unwrap();
-                                let (mut data, span, channel) = event;
-
-                                return_channels.push((channel, data.len()));
-                                waiting_spans.push(span);
-                                data_buffer.append(&mut data);
-
-                                if data_buffer.len() >= capacity {
-                                    break;
-                                }
-                            }
-
-                            _ = ::tokio::time::sleep(remaining_duration) => {
-                                break;
-                            }
-                        }
-                    }
-
-                    if return_channels.is_empty() {
-                        continue;
-                    }
-
-                    let mut data = vec![];
-                    let mut spans = vec![];
-                    let mut channels = vec![];
-
-                    std::mem::swap(&mut data, &mut data_buffer);
-                    std::mem::swap(&mut spans, &mut waiting_spans);
-                    std::mem::swap(&mut channels, &mut return_channels);
-
-                    let permit = semaphore.clone().acquire_owned().await.unwrap();
-                    tokio::task::spawn(async move {
-                        let _permit = permit;
-                        let batched_span = ::batched::tracing::info_span!(#batched_span_name, count = data.len());
-                        for mut span in spans {
-                            ::batched::tracing::TracingSpan::link_span(&mut span, &batched_span);
-                        }
-
-                        let future = #inner_batched(data);
-                        let mut result = ::batched::tracing::Instrument::instrument(future, batched_span).await;
-                        #propagate_result
-                    });
-                }
-            });
-
-            sender
+        #executor
+
+        #[doc(hidden)]
+        fn #executor_registry_fn() -> &'static #registry_type {
+            static #executor_producer_channel: ::std::sync::LazyLock<#registry_type> =
+                ::std::sync::LazyLock::new(Default::default);
+            &#executor_producer_channel
+        }
+
+        #[doc(hidden)]
+        #[allow(clippy::ptr_arg)]
+        fn #executor_lookup_fn(#receiver #key_ref_param) -> #batcher_type {
+            #registry_call.get_or_insert_with(#registry_key, || #spawn_executor)
         }
+
+        /// Processes pending calls of every executor right away and waits for running batches
+        #visibility async fn #flush_fn() {
+            #registry_call.flush().await
+        }
+
+        /// Processes pending calls of every executor and waits for running batches, calls made
+        /// afterwards are rejected
+        #visibility async fn #shutdown_fn() {
+            #registry_call.shutdown().await
+        }
+
+        /// Statistics of every executor combined
+        #visibility fn #stats_fn() -> ::batched::stats::BatchStats {
+            #registry_call.stats()
+        }
+    }
+}
+
+/// Joins the partition key (if the function has one) and the batched argument in the order they
+/// were declared in
+fn with_key(call_function: &Function, key: impl ToTokens, batched: impl ToTokens) -> TokenStream {
+    match &call_function.key_arg {
+        Some(key_arg) if key_arg.first => quote! { #key, #batched },
+        Some(_) => quote! { #batched, #key },
+        None => batched.into_token_stream(),
     }
 }
 
@@ -10,8 +10,17 @@ use proc_macro::TokenStream;
 
 #[proc_macro_attribute]
 pub fn batched(attributes: TokenStream, item: TokenStream) -> TokenStream {
-    let attributes = Attributes::parse(attributes.into());
-    let function = Function::parse(item.into());
+    let attributes = match Attributes::parse(attributes.into()) {
+        Ok(attributes) => attributes,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let function = match Function::parse(item.into()) {
+        Ok(function) => function,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    if let Err(error) = attributes.validate(&function) {
+        return error.to_compile_error().into();
+    }
     let _identifier = function.identifier.clone();
 
     let result = build_code(function, attributes).into();
@@ -1,13 +1,15 @@
 use std::collections::BTreeMap;
 
-use proc_macro2::TokenStream;
-use quote::ToTokens;
+use proc_macro2::{Span, TokenStream};
+use quote::{ToTokens, quote};
 use syn::{
-    FnArg, GenericArgument, ItemFn, Meta, Pat, PathArguments, ReturnType, Token, Type,
-    parse::Parser, punctuated::Punctuated,
+    Error, Expr, ExprLit, FnArg, GenericArgument, ItemFn, Lit, Meta, Pat, PathArguments,
+    PathSegment, ReturnType, Token, Type, parse::Parser, punctuated::Punctuated,
 };
 
-use crate::utils::expr_to_u64;
+use crate::utils::{
+    expr_to_duration, expr_to_rate, expr_to_u64, expr_to_usize, flag, name_value,
+};
 
 #[derive(Debug)]
 pub struct Function {
@@ -15,12 +17,24 @@ pub struct Function {
     pub identifier: String,
     pub visibility: TokenStream,
     pub inner: TokenStream,
+    pub receiver: Option<TokenStream>,
+    pub key_arg: Option<KeyArgument>,
     pub batched_arg: TokenStream,
     pub batched_arg_name: String,
     pub batched_arg_type: TokenStream,
     pub returned: FunctionResult,
 }
 
+/// Extra (non-`Vec`) argument batches are partitioned by
+#[derive(Debug)]
+pub struct KeyArgument {
+    pub arg: TokenStream,
+    pub name: String,
+    pub _type: TokenStream,
+    /// Whether the key comes before the batched argument
+    pub first: bool,
+}
+
 #[derive(Debug)]
 pub struct FunctionResult {
     pub result_type: FunctionResultType,
@@ -34,27 +48,46 @@ pub enum FunctionResultType {
     Result(Box<FunctionResult>, TokenStream, Option<TokenStream>)
 }
 
-fn inner_shared_error(_type: &Type) -> Option<TokenStream> {
+fn inner_shared_error(_type: &Type) -> syn::Result<Option<TokenStream>> {
     let type_path = match _type {
         Type::Path(path) => path,
-        _ => unimplemented!(),
+        _ => return Ok(None),
+    };
+    let Some(path) = type_path.path.segments.last() else {
+        return Ok(None);
     };
-    let path = type_path.path.segments.last().unwrap();
     if path.ident != "SharedError" {
-        return None;
+        return Ok(None);
     }
 
     match &path.arguments {
         PathArguments::AngleBracketed(path_args) => {
-            Some(path_args.args.clone().into_token_stream())
+            Ok(Some(path_args.args.clone().into_token_stream()))
         }
-        _ => unimplemented!(),
+        _ => Err(Error::new_spanned(path, "expected `SharedError<E>`")),
+    }
+}
+
+fn angle_bracketed<'a>(
+    segment: &'a PathSegment,
+    expected: &str,
+) -> syn::Result<&'a Punctuated<GenericArgument, Token![,]>> {
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => Ok(&arguments.args),
+        _ => Err(Error::new_spanned(segment, format!("expected `{expected}`"))),
     }
 }
 
 impl Function {
-    pub fn parse(tokens: TokenStream) -> Self {
-        let function: ItemFn = syn::parse2(tokens).expect("invalid function");
+    pub fn parse(tokens: TokenStream) -> syn::Result<Self> {
+        let function: ItemFn = syn::parse2(tokens)?;
+
+        if !function.sig.generics.params.is_empty() {
+            return Err(Error::new_spanned(
+                &function.sig.generics,
+                "batched functions can't be generic",
+            ));
+        }
 
         let macros = function
             .attrs
@@ -64,40 +97,28 @@ impl Function {
 
         let visibility = function.vis.into_token_stream();
         let identifier = function.sig.ident.to_string();
-        let args = function.sig.inputs;
         let inner = function.block.to_token_stream();
 
-        fn parsed_returned(_type: &Type) -> FunctionResult {
+        fn parsed_returned(_type: &Type) -> syn::Result<FunctionResult> {
             let tokens = _type.clone().into_token_stream();
             let result_type = match _type {
                 Type::Path(type_path) => {
                     let path = type_path.path.segments.first().unwrap();
 
                     if path.ident == "Vec" {
-                        let inner = match &path.arguments {
-                            PathArguments::AngleBracketed(b) => b,
-                            _ => unimplemented!(),
-                        };
-                        let inner = inner.args.to_token_stream();
+                        let inner = angle_bracketed(path, "Vec<T>")?;
+                        let inner = inner.to_token_stream();
                         FunctionResultType::VectorRaw(inner)
                     } else if path.ident == "Result" {
-                        let inner = match &path.arguments {
-                            PathArguments::AngleBracketed(b) => b,
-                            _ => unimplemented!(),
-                        };
-
-                        let output = inner.args.get(0).unwrap();
-                        let error = inner.args.get(1).unwrap();
-                        let error = match error {
-                            GenericArgument::Type(error) => error,
-                            _ => unimplemented!(),
+                        let inner = angle_bracketed(path, "Result<T, E>")?;
+                        let (Some(GenericArgument::Type(output)), Some(GenericArgument::Type(error)), 2) =
+                            (inner.get(0), inner.get(1), inner.len())
+                        else {
+                            return Err(Error::new_spanned(path, "expected `Result<T, E>`"));
                         };
 
-                        let output = match output {
-                            GenericArgument::Type(_type) => parsed_returned(_type),
-                            _ => unimplemented!(),
-                        };
-                        let inner_shared_error = inner_shared_error(error);
+                        let output = parsed_returned(output)?;
+                        let inner_shared_error = inner_shared_error(error)?;
                         let error = error.into_token_stream();
 
                         FunctionResultType::Result(Box::new(output), error, inner_shared_error)
@@ -105,168 +126,537 @@ impl Function {
                         FunctionResultType::Raw(_type.into_token_stream())
                     }
                 }
+                Type::Reference(_) => {
+                    return Err(Error::new_spanned(
+                        _type,
+                        "batched functions can't return references, the result is sent to other tasks",
+                    ));
+                }
                 _ => FunctionResultType::Raw(_type.into_token_stream()),
             };
 
-            FunctionResult {
+            Ok(FunctionResult {
                 tokens,
                 result_type,
-            }
+            })
         }
-        let returned = match function.sig.output {
+        let returned = match &function.sig.output {
             ReturnType::Default => FunctionResult {
                 tokens: syn::parse_str("()").unwrap(),
                 result_type: FunctionResultType::Raw(syn::parse_str("()").unwrap()),
             },
-            ReturnType::Type(_, _type) => parsed_returned(&_type),
+            ReturnType::Type(_, _type) => parsed_returned(_type)?,
         };
 
-        let mut batched_arg: Option<TokenStream> = None;
-        let mut batched_arg_name: Option<String> = None;
-        let mut batched_arg_type: Option<TokenStream> = None;
+        let mut receiver: Option<TokenStream> = None;
+        let mut key_arg: Option<KeyArgument> = None;
+        let mut batched_arg: Option<(TokenStream, String, TokenStream)> = None;
 
-        for arg in args {
-            if let FnArg::Receiver(_) = arg {
-                panic!("self reference functions are not supported")
-            } else if let FnArg::Typed(arg) = arg {
-                if batched_arg_type.is_some() {
-                    panic!("function may only contain a single argument")
+        for arg in &function.sig.inputs {
+            if let FnArg::Receiver(arg) = arg {
+                if arg.reference.is_none() || arg.mutability.is_some() || arg.colon_token.is_some() {
+                    return Err(Error::new_spanned(arg, "batched methods must take `&self`"));
                 }
 
-                batched_arg = Some(arg.to_token_stream());
-                batched_arg_name = match &*arg.pat {
-                    Pat::Ident(pat_ident) => Some(pat_ident.ident.to_string()),
-                    _ => panic!("unsupport argument name"),
+                receiver = Some(arg.to_token_stream());
+            } else if let FnArg::Typed(arg) = arg {
+                let arg_name = match &*arg.pat {
+                    Pat::Ident(pat_ident) => pat_ident.ident.to_string(),
+                    pat => {
+                        return Err(Error::new_spanned(
+                            pat,
+                            "unsupported argument pattern, expected an identifier",
+                        ));
+                    }
                 };
 
-                if let syn::Type::Path(type_path) = &*arg.ty {
-                    let segment = type_path.path.segments.last().unwrap();
-                    if segment.ident == "Vec" {
-                        let vec_type = match &segment.arguments {
-                            PathArguments::AngleBracketed(a) => a,
-                            _ => unreachable!(),
-                        };
-                        let vec_type = vec_type.args.first().unwrap();
-                        batched_arg_type = Some(vec_type.to_token_stream());
-                        continue;
-                    }
+                let mut vec_type = None;
+                if let syn::Type::Path(type_path) = &*arg.ty
+                    && let Some(segment) = type_path.path.segments.last()
+                    && segment.ident == "Vec"
+                {
+                    let segment_type = angle_bracketed(segment, "Vec<T>")?;
+                    vec_type = Some(segment_type.to_token_stream());
                 }
 
-                panic!("function argument must be Vec<T>")
-            }
-        }
+                if let Some(vec_type) = vec_type {
+                    if batched_arg.is_some() {
+                        return Err(Error::new_spanned(
+                            arg,
+                            "function may only contain a single Vec<T> argument",
+                        ));
+                    }
+
+                    batched_arg = Some((arg.to_token_stream(), arg_name, vec_type));
+                } else {
+                    if key_arg.is_some() {
+                        return Err(Error::new_spanned(
+                            arg,
+                            "function may only contain a single key argument besides the Vec<T> argument",
+                        ));
+                    }
 
-        if batched_arg_type.is_none() {
-            panic!("function must contain a single argument")
+                    key_arg = Some(KeyArgument {
+                        arg: arg.to_token_stream(),
+                        name: arg_name,
+                        _type: arg.ty.to_token_stream(),
+                        first: batched_arg.is_none(),
+                    });
+                }
+            }
         }
 
-        let batched_arg = batched_arg.unwrap();
-        let batched_arg_name = batched_arg_name.unwrap();
-        let batched_arg_type = batched_arg_type.unwrap();
+        let Some((batched_arg, batched_arg_name, batched_arg_type)) = batched_arg else {
+            let span = match &key_arg {
+                Some(key_arg) => key_arg.arg.clone(),
+                None => function.sig.to_token_stream(),
+            };
+            return Err(Error::new_spanned(span, "function argument must be Vec<T>"));
+        };
 
-        Self {
+        Ok(Self {
             macros,
             identifier,
             visibility,
+            receiver,
+            key_arg,
             batched_arg,
             batched_arg_name,
             batched_arg_type,
             returned,
             inner,
-        }
+        })
     }
 }
 
 #[derive(Debug)]
 pub struct Attributes {
-    pub limit: Option<usize>,
-    pub concurrent_limit: Option<usize>,
+    pub limit: Option<TokenStream>,
+    pub min: Option<TokenStream>,
+    pub max_wait: Option<TokenStream>,
+    pub weight: Option<(Meta, Expr)>,
+    pub max_weight: Option<TokenStream>,
+    pub concurrent_limit: Option<TokenStream>,
+    pub rate: Option<TokenStream>,
+    pub burst: Option<TokenStream>,
     pub asynchronous: bool,
     pub passthrough: bool,
-    pub default_window: u64,
-    pub windows: BTreeMap<u64, u64>,
+    pub cancel_on_drop: bool,
+    pub fallible: bool,
+    pub timeout: Option<TokenStream>,
+    pub queue: Option<TokenStream>,
+    pub retry: Option<RetryAttribute>,
+    pub split_on_error: Option<Meta>,
+    pub dedupe: bool,
+    pub cache: Option<CacheAttribute>,
+    pub default_window: TokenStream,
+    pub adaptive: Option<TokenStream>,
+    pub windows: BTreeMap<u64, TokenStream>,
+    pub instance: Option<Expr>,
 }
 
 impl Attributes {
-    pub fn parse(tokens: TokenStream) -> Self {
-        let mut limit: Option<usize> = None;
-        let mut concurrent_limit: Option<usize> = None;
+    pub fn parse(tokens: TokenStream) -> syn::Result<Self> {
+        let mut limit: Option<TokenStream> = None;
+        let mut min: Option<TokenStream> = None;
+        let mut max_wait: Option<TokenStream> = None;
+        let mut weight: Option<(Meta, Expr)> = None;
+        let mut max_weight: Option<TokenStream> = None;
+        let mut concurrent_limit: Option<TokenStream> = None;
+        let mut rate: Option<TokenStream> = None;
+        let mut burst: Option<TokenStream> = None;
         let mut asynchronous = false;
         let mut passthrough = false;
-        let mut default_window: Option<u64> = None;
+        let mut cancel_on_drop = false;
+        let mut fallible = false;
+        let mut timeout: Option<TokenStream> = None;
+        let mut queue: Option<TokenStream> = None;
+        let mut retry: Option<RetryAttribute> = None;
+        let mut split_on_error: Option<Meta> = None;
+        let mut dedupe = false;
+        let mut cache: Option<CacheAttribute> = None;
+        let mut default_window: Option<TokenStream> = None;
+        let mut adaptive: Option<(TokenStream, TokenStream)> = None;
         let mut windows = BTreeMap::new();
+        let mut instance: Option<Expr> = None;
 
         static WINDOW_ATTR: &str = "window";
         static LIMIT_ATTR: &str = "limit";
+        static MIN_ATTR: &str = "min";
+        static MAX_WAIT_ATTR: &str = "max_wait";
+        static WEIGHT_ATTR: &str = "weight";
+        static MAX_WEIGHT_ATTR: &str = "max_weight";
         static CONCURRENT_LIMIT_ATTR: &str = "concurrent";
+        static RATE_ATTR: &str = "rate";
+        static BURST_ATTR: &str = "burst";
         static ASYNCHRONOUS_ATTR: &str = "asynchronous";
         static PASSTHROUGH_ATTR: &str = "passthrough";
-
-        let parser = Punctuated::<Meta, Token![,]>::parse_separated_nonempty;
-        let attributes = parser.parse(tokens.into()).unwrap();
+        static INSTANCE_ATTR: &str = "instance";
+        static CANCEL_ON_DROP_ATTR: &str = "cancel_on_drop";
+        static FALLIBLE_ATTR: &str = "fallible";
+        static TIMEOUT_ATTR: &str = "timeout";
+        static QUEUE_ATTR: &str = "queue";
+        static RETRY_ATTR: &str = "retry";
+        static SPLIT_ON_ERROR_ATTR: &str = "split_on_error";
+        static ADAPTIVE_ATTR: &str = "adaptive";
+        static DEDUPE_ATTR: &str = "dedupe";
+        static CACHE_ATTR: &str = "cache";
+
+        let parser = Punctuated::<Meta, Token![,]>::parse_terminated;
+        let attributes = parser.parse2(tokens)?;
         let attributes = attributes.into_iter().collect::<Vec<Meta>>();
 
         for attr in &attributes {
             let path = attr.path();
             if path.is_ident(LIMIT_ATTR) {
-                let value = match attr {
-                    Meta::NameValue(attr) => &attr.value,
-                    _ => unimplemented!(),
-                };
-
-                limit = expr_to_u64(value).map(|u| u as usize);
+                let value = name_value(attr)?;
+                limit = Some(expr_to_usize(value)?);
+            } else if path.is_ident(MIN_ATTR) {
+                let value = name_value(attr)?;
+                min = Some(expr_to_usize(value)?);
+            } else if path.is_ident(MAX_WAIT_ATTR) {
+                let value = name_value(attr)?;
+                max_wait = Some(expr_to_duration(value)?);
+            } else if path.is_ident(WEIGHT_ATTR) {
+                let value = name_value(attr)?;
+                weight = Some((attr.clone(), value.clone()));
+            } else if path.is_ident(MAX_WEIGHT_ATTR) {
+                let value = name_value(attr)?;
+                max_weight = Some(expr_to_u64(value)?);
             } else if path.is_ident(CONCURRENT_LIMIT_ATTR) {
-                let value = match attr {
-                    Meta::NameValue(attr) => &attr.value,
-                    _ => unimplemented!(),
-                };
-
-                concurrent_limit = expr_to_u64(value).map(|u| u as usize);
+                let value = name_value(attr)?;
+                concurrent_limit = Some(expr_to_usize(value)?);
+            } else if path.is_ident(RATE_ATTR) {
+                let value = name_value(attr)?;
+                rate = Some(expr_to_rate(value)?);
+            } else if path.is_ident(BURST_ATTR) {
+                let value = name_value(attr)?;
+                burst = Some(expr_to_usize(value)?);
             } else if path.is_ident(ASYNCHRONOUS_ATTR) {
-                asynchronous = true;
+                asynchronous = flag(attr)?;
             } else if path.is_ident(PASSTHROUGH_ATTR) {
-                passthrough = true;
+                passthrough = flag(attr)?;
+            } else if path.is_ident(CANCEL_ON_DROP_ATTR) {
+                cancel_on_drop = flag(attr)?;
+            } else if path.is_ident(FALLIBLE_ATTR) {
+                fallible = flag(attr)?;
+            } else if path.is_ident(TIMEOUT_ATTR) {
+                let value = name_value(attr)?;
+                timeout = Some(expr_to_duration(value)?);
+            } else if path.is_ident(QUEUE_ATTR) {
+                let value = name_value(attr)?;
+                queue = Some(expr_to_usize(value)?);
+            } else if path.is_ident(RETRY_ATTR) {
+                retry = Some(RetryAttribute::parse(attr)?);
+            } else if path.is_ident(SPLIT_ON_ERROR_ATTR) {
+                split_on_error = flag(attr)?.then(|| attr.clone());
+            } else if path.is_ident(CACHE_ATTR) {
+                cache = Some(CacheAttribute::parse(attr)?);
+            } else if path.is_ident(DEDUPE_ATTR) {
+                dedupe = flag(attr)?;
+            } else if path.is_ident(ADAPTIVE_ATTR) {
+                adaptive = Some(adaptive_window(attr)?);
+            } else if path.is_ident(INSTANCE_ATTR) {
+                let value = name_value(attr)?;
+                instance = Some(value.clone());
             } else if path.is_ident(WINDOW_ATTR) {
-                let value = match attr {
-                    Meta::NameValue(attr) => &attr.value,
-                    _ => unimplemented!(),
-                };
-
-                let window_duration_ms = expr_to_u64(value);
-                default_window = window_duration_ms;
+                let value = name_value(attr)?;
+                default_window = Some(expr_to_duration(value)?);
             } else if let Some(ident) = path.get_ident().map(|i| i.to_string())
                 && ident.starts_with(WINDOW_ATTR)
             {
-                let value = match attr {
-                    Meta::NameValue(attr) => &attr.value,
-                    _ => unimplemented!(),
-                };
+                let value = name_value(attr)?;
 
                 let call_size = ident.replace(WINDOW_ATTR, "");
-                let call_size = call_size.parse::<u64>().unwrap();
-                let call_window = expr_to_u64(value).expect("expected u64");
+                let call_size = call_size.parse::<u64>().map_err(|_| {
+                    Error::new_spanned(path, "expected `window<N>`, where N is the batch size")
+                })?;
+                let call_window = expr_to_duration(value)?;
 
                 let unsorted = windows
                     .iter()
                     .find(|(_call_size, _)| **_call_size > call_size);
                 if unsorted.is_some() {
-                    panic!("dynamic window call size must be sorted")
+                    return Err(Error::new_spanned(
+                        path,
+                        "dynamic window call size must be sorted",
+                    ));
                 }
 
                 windows.insert(call_size, call_window);
+            } else {
+                return Err(Error::new_spanned(path, "unknown batched attribute"));
             }
         }
 
-        let default_window = default_window
-            .expect("expected required attribute: window");
+        // An adaptive window starts at its minimum and replaces the (then optional) window
+        let (adaptive_min, adaptive) = adaptive.unzip();
+        if let Some((attr, _)) = &weight
+            && max_weight.is_none()
+        {
+            return Err(Error::new_spanned(attr, "weight requires max_weight"));
+        }
+
+        let default_window = default_window.or(adaptive_min).ok_or_else(|| {
+            Error::new(Span::call_site(), "expected required attribute: window")
+        })?;
 
-        Self {
+        Ok(Self {
             limit,
+            min,
+            max_wait,
+            weight,
+            max_weight,
             concurrent_limit,
+            rate,
+            burst,
             asynchronous,
             passthrough,
+            cancel_on_drop,
+            fallible,
+            timeout,
+            queue,
+            retry,
+            split_on_error,
+            dedupe,
+            cache,
             default_window,
+            adaptive,
             windows,
+            instance,
+        })
+    }
+
+    /// Checks the attributes that depend on the signature of the function
+    pub fn validate(&self, function: &Function) -> syn::Result<()> {
+        let returns_result = matches!(
+            function.returned.result_type,
+            FunctionResultType::Result(..)
+        );
+        if let Some(retry) = &self.retry
+            && !returns_result
+        {
+            return Err(Error::new_spanned(
+                &retry.attribute,
+                "retry requires the batched function to return a `Result`",
+            ));
+        }
+        if let Some(split_on_error) = &self.split_on_error
+            && !returns_result
+        {
+            return Err(Error::new_spanned(
+                split_on_error,
+                "split_on_error requires the batched function to return a `Result`",
+            ));
+        }
+
+        if let Some(receiver) = &function.receiver
+            && self.instance.is_none()
+        {
+            return Err(Error::new_spanned(
+                receiver,
+                "methods require `instance = ...`, the key of the executor of an instance",
+            ));
+        }
+
+        if let Some(cache) = &self.cache {
+            let returns_vec = match &function.returned.result_type {
+                FunctionResultType::VectorRaw(_) => true,
+                FunctionResultType::Result(output, _, _) => {
+                    matches!(output.result_type, FunctionResultType::VectorRaw(_))
+                }
+                FunctionResultType::Raw(_) => false,
+            };
+            let unsupported = if !returns_vec {
+                Some("cache requires the batched function to return a `Vec` (or `Result<Vec<T>, E>`)")
+            } else if self.asynchronous {
+                Some("cache is not supported on asynchronous functions")
+            } else if function.receiver.is_some() {
+                Some("cache is not supported on methods")
+            } else {
+                None
+            };
+            if let Some(message) = unsupported {
+                return Err(Error::new_spanned(&cache.attribute, message));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `cache(ttl = "5s", capacity = 10_000)`
+#[derive(Debug)]
+pub struct CacheAttribute {
+    pub attribute: Meta,
+    pub ttl: TokenStream,
+    pub capacity: Option<TokenStream>,
+}
+
+impl CacheAttribute {
+    fn parse(attribute: &Meta) -> syn::Result<Self> {
+        let Meta::List(list) = attribute else {
+            return Err(Error::new_spanned(attribute, "expected `cache(ttl = ...)`"));
+        };
+
+        static TTL_ATTR: &str = "ttl";
+        static CAPACITY_ATTR: &str = "capacity";
+
+        let mut ttl: Option<TokenStream> = None;
+        let mut capacity: Option<TokenStream> = None;
+
+        let parser = Punctuated::<Meta, Token![,]>::parse_terminated;
+        for attr in list.parse_args_with(parser)? {
+            let path = attr.path();
+            let value = name_value(&attr)?;
+            if path.is_ident(TTL_ATTR) {
+                ttl = Some(expr_to_duration(value)?);
+            } else if path.is_ident(CAPACITY_ATTR) {
+                capacity = Some(expr_to_usize(value)?);
+            } else {
+                return Err(Error::new_spanned(path, "unknown cache attribute"));
+            }
+        }
+
+        let ttl = ttl.ok_or_else(|| Error::new_spanned(attribute, "expected cache `ttl`"))?;
+        Ok(Self {
+            attribute: attribute.clone(),
+            ttl,
+            capacity,
+        })
+    }
+}
+
+/// `adaptive(min = 1, max = 50, target_size = 100)` or `adaptive(..., target_latency = "20ms")`,
+/// returned as the minimum window and the `AdaptiveWindow` expression
+fn adaptive_window(attribute: &Meta) -> syn::Result<(TokenStream, TokenStream)> {
+    let Meta::List(list) = attribute else {
+        return Err(Error::new_spanned(attribute, "expected `adaptive(...)`"));
+    };
+
+    static MIN_ATTR: &str = "min";
+    static MAX_ATTR: &str = "max";
+    static TARGET_SIZE_ATTR: &str = "target_size";
+    static TARGET_LATENCY_ATTR: &str = "target_latency";
+
+    let mut min = quote! { ::std::time::Duration::ZERO };
+    let mut max: Option<TokenStream> = None;
+    let mut target: Option<TokenStream> = None;
+
+    let parser = Punctuated::<Meta, Token![,]>::parse_terminated;
+    for attr in list.parse_args_with(parser)? {
+        let path = attr.path();
+        let value = name_value(&attr)?;
+        if path.is_ident(MIN_ATTR) {
+            min = expr_to_duration(value)?;
+        } else if path.is_ident(MAX_ATTR) {
+            max = Some(expr_to_duration(value)?);
+        } else if target.is_some()
+            && (path.is_ident(TARGET_SIZE_ATTR) || path.is_ident(TARGET_LATENCY_ATTR))
+        {
+            return Err(Error::new_spanned(
+                path,
+                "expected only one of target_size or target_latency",
+            ));
+        } else if path.is_ident(TARGET_SIZE_ATTR) {
+            let size = expr_to_usize(value)?;
+            target = Some(quote! { ::batched::config::AdaptiveTarget::BatchSize(#size) });
+        } else if path.is_ident(TARGET_LATENCY_ATTR) {
+            let latency = expr_to_duration(value)?;
+            target = Some(quote! { ::batched::config::AdaptiveTarget::Latency(#latency) });
+        } else {
+            return Err(Error::new_spanned(path, "unknown adaptive attribute"));
         }
     }
-}
\ No newline at end of file
+
+    let max = max.ok_or_else(|| Error::new_spanned(attribute, "expected adaptive `max`"))?;
+    let target = target.ok_or_else(|| {
+        Error::new_spanned(attribute, "expected adaptive `target_size` or `target_latency`")
+    })?;
+
+    let adaptive = quote! {
+        ::batched::config::AdaptiveWindow {
+            min: #min,
+            max: #max,
+            target: #target,
+        }
+    };
+    Ok((min, adaptive))
+}
+
+/// `retry` or `retry(attempts = 3, backoff = "exponential", base = "50ms", predicate = path)`
+#[derive(Debug)]
+pub struct RetryAttribute {
+    pub attribute: Meta,
+    pub attempts: Option<TokenStream>,
+    pub backoff: Option<TokenStream>,
+    pub base: Option<TokenStream>,
+    pub predicate: Option<Expr>,
+}
+
+impl RetryAttribute {
+    fn parse(attribute: &Meta) -> syn::Result<Self> {
+        let mut retry = Self {
+            attribute: attribute.clone(),
+            attempts: None,
+            backoff: None,
+            base: None,
+            predicate: None,
+        };
+
+        let list = match attribute {
+            Meta::Path(_) => return Ok(retry),
+            Meta::List(list) => list,
+            Meta::NameValue(_) => {
+                return Err(Error::new_spanned(attribute, "expected `retry(...)`"));
+            }
+        };
+
+        static ATTEMPTS_ATTR: &str = "attempts";
+        static BACKOFF_ATTR: &str = "backoff";
+        static BASE_ATTR: &str = "base";
+        static PREDICATE_ATTR: &str = "predicate";
+
+        let parser = Punctuated::<Meta, Token![,]>::parse_terminated;
+        for attr in list.parse_args_with(parser)? {
+            let path = attr.path();
+            let value = name_value(&attr)?;
+            if path.is_ident(ATTEMPTS_ATTR) {
+                retry.attempts = Some(quote! {
+                    <u32 as ::std::convert::TryFrom<_>>::try_from(#value)
+                        .expect("batched attribute out of range")
+                });
+            } else if path.is_ident(BACKOFF_ATTR) {
+                let backoff = match value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit_str),
+                        ..
+                    }) => {
+                        let backoff = match lit_str.value().as_str() {
+                            "constant" => quote! { Constant },
+                            "linear" => quote! { Linear },
+                            "exponential" => quote! { Exponential },
+                            _ => {
+                                return Err(Error::new_spanned(
+                                    lit_str,
+                                    "expected \"constant\", \"linear\" or \"exponential\"",
+                                ));
+                            }
+                        };
+                        quote! { ::batched::retry::Backoff::#backoff }
+                    }
+                    value => quote! { #value },
+                };
+                retry.backoff = Some(backoff);
+            } else if path.is_ident(BASE_ATTR) {
+                retry.base = Some(expr_to_duration(value)?);
+            } else if path.is_ident(PREDICATE_ATTR) {
+                retry.predicate = Some(value.clone());
+            } else {
+                return Err(Error::new_spanned(path, "unknown retry attribute"));
+            }
+        }
+
+        Ok(retry)
+    }
+}
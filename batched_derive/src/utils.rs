@@ -1,13 +1,124 @@
-use syn::{Expr, ExprLit, Lit};
-
-pub fn expr_to_u64(expr: &Expr) -> Option<u64> {
-    if let Expr::Lit(ExprLit {
-        lit: Lit::Int(lit_int),
-        ..
-    }) = expr
-    {
-        lit_int.base10_parse::<u64>().ok()
-    } else {
-        None
+use proc_macro2::TokenStream;
+use quote::{ToTokens, quote};
+use syn::{Error, Expr, ExprLit, Lit, Meta};
+
+/// Duration (`std::time::Duration`) expression for a window attribute. Integer literals are
+/// milliseconds and string literals are parsed as human readable durations at compile time. Any
+/// other expression is evaluated when the executor starts
+pub fn expr_to_duration(expr: &Expr) -> syn::Result<TokenStream> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit_int),
+            ..
+        }) => {
+            let millis = lit_int.base10_parse::<u64>()?;
+            Ok(quote! { ::std::time::Duration::from_millis(#millis) })
+        }
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit_str),
+            ..
+        }) => {
+            let duration = humantime::parse_duration(&lit_str.value()).map_err(|error| {
+                Error::new_spanned(lit_str, format!("invalid duration: {error}"))
+            })?;
+            let (secs, nanos) = (duration.as_secs(), duration.subsec_nanos());
+            Ok(quote! { ::std::time::Duration::new(#secs, #nanos) })
+        }
+        Expr::Lit(_) => Err(Error::new_spanned(
+            expr,
+            "expected milliseconds or a duration string",
+        )),
+        expr => Ok(quote! { ::batched::IntoDuration::into_duration(#expr) }),
+    }
+}
+
+/// `batched::config::Rate` expression for a rate attribute. String literals (`"10/s"`,
+/// `"5/100ms"`) are parsed at compile time, any other expression is evaluated when the executor
+/// starts
+pub fn expr_to_rate(expr: &Expr) -> syn::Result<TokenStream> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(lit_str),
+            ..
+        }) => {
+            let (count, period) = batched_parse::parse_rate(&lit_str.value())
+                .map_err(|error| Error::new_spanned(lit_str, error))?;
+            let (secs, nanos) = (period.as_secs(), period.subsec_nanos());
+            Ok(quote! {
+                ::batched::config::Rate {
+                    count: #count,
+                    period: ::std::time::Duration::new(#secs, #nanos),
+                }
+            })
+        }
+        Expr::Lit(_) => Err(Error::new_spanned(expr, "expected a rate string such as \"10/s\"")),
+        expr => Ok(quote! { ::batched::config::IntoRate::into_rate(#expr) }),
+    }
+}
+
+/// `usize` expression for a count attribute. Non-literal expressions are evaluated when the
+/// executor starts
+pub fn expr_to_usize(expr: &Expr) -> syn::Result<TokenStream> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit_int),
+            ..
+        }) => {
+            let value = lit_int.base10_parse::<usize>()?;
+            Ok(quote! { #value })
+        }
+        Expr::Lit(_) => Err(Error::new_spanned(expr, "expected integer")),
+        expr => Ok(quote! {
+            <usize as ::std::convert::TryFrom<_>>::try_from(#expr)
+                .expect("batched attribute out of range")
+        }),
+    }
+}
+
+/// `u64` expression for a weight attribute. Non-literal expressions are evaluated when the
+/// executor starts
+pub fn expr_to_u64(expr: &Expr) -> syn::Result<TokenStream> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(lit_int),
+            ..
+        }) => {
+            let value = lit_int.base10_parse::<u64>()?;
+            Ok(quote! { #value })
+        }
+        Expr::Lit(_) => Err(Error::new_spanned(expr, "expected integer")),
+        expr => Ok(quote! {
+            <u64 as ::std::convert::TryFrom<_>>::try_from(#expr)
+                .expect("batched attribute out of range")
+        }),
+    }
+}
+
+/// Value of a `name = value` attribute
+pub fn name_value(attr: &Meta) -> syn::Result<&Expr> {
+    match attr {
+        Meta::NameValue(attr) => Ok(&attr.value),
+        _ => {
+            let path = attr.path().to_token_stream();
+            Err(Error::new_spanned(attr, format!("expected `{path} = ...`")))
+        }
+    }
+}
+
+/// Boolean attribute, either `name` or `name = true|false`
+pub fn flag(attr: &Meta) -> syn::Result<bool> {
+    match attr {
+        Meta::Path(_) => Ok(true),
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Bool(lit_bool),
+                ..
+            }) => Ok(lit_bool.value),
+            value => Err(Error::new_spanned(value, "expected `true` or `false`")),
+        },
+        Meta::List(_) => {
+            let path = attr.path().to_token_stream();
+            Err(Error::new_spanned(attr, format!("expected `{path}`")))
+        }
     }
 }
@@ -0,0 +1,24 @@
+use std::time::Duration;
+
+/// Parses a rate `<count>/<period>` into its count and period, where the period is a unit
+/// ("10/s", "600/m") or a human readable duration ("5/100ms"). The count must be at least 1
+pub fn parse_rate(rate: &str) -> Result<(u32, Duration), String> {
+    let (count, period) = rate
+        .split_once('/')
+        .ok_or_else(|| "expected `<count>/<period>`".to_string())?;
+    let count: u32 = count
+        .trim()
+        .parse()
+        .map_err(|error| format!("invalid rate count: {error}"))?;
+    if count == 0 {
+        return Err("rate count must be at least 1".to_string());
+    }
+
+    let period = period.trim();
+    let period = match period.starts_with(|c: char| c.is_ascii_digit()) {
+        true => humantime::parse_duration(period),
+        false => humantime::parse_duration(&format!("1{period}")),
+    };
+    let period = period.map_err(|error| format!("invalid rate period: {error}"))?;
+    Ok((count, period))
+}
@@ -0,0 +1,646 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use batched::{
+    Batcher, BatcherMap, Error, Priority,
+    batcher::{Cloned, Split},
+    cache::Cache,
+    config::{AdaptiveTarget, AdaptiveWindow, Rate},
+};
+
+#[tokio::test]
+async fn cloned() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.iter().sum::<u32>())
+        .window(Duration::from_millis(100))
+        .limit(1000)
+        .build();
+
+    let (a, b, c) = tokio::join!(batcher.call(1), batcher.call(2), batcher.call_many(vec![3, 4]));
+    assert_eq!((a, b, c), (10, 10, 10));
+}
+
+#[tokio::test]
+async fn split() {
+    let batcher = Batcher::builder_split(async |numbers: Vec<u32>| {
+        numbers.into_iter().map(|n| n * 2).collect::<Vec<_>>()
+    })
+    .window(Duration::from_millis(100))
+    .build();
+
+    let (a, b) = tokio::join!(batcher.call(1), batcher.call_many(vec![2, 3]));
+    assert_eq!(a, 2);
+    assert_eq!(b, vec![4, 6]);
+
+    // Missing results fail every caller instead of handing out another caller's results
+    let batcher = Batcher::builder_split(async |ids: Vec<u32>| ids[1..].to_vec())
+        .window(Duration::from_millis(100))
+        .build();
+    let (a, b) = tokio::join!(batcher.try_call(1), batcher.try_call_many(vec![7, 8]));
+    let missing = Error::MissingResults {
+        expected: 3,
+        returned: 2,
+    };
+    assert_eq!(a, Err(missing.clone()));
+    assert_eq!(b, Err(missing));
+}
+
+#[tokio::test]
+async fn windows_and_limit() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_secs(10))
+        .window_for(1, Duration::from_millis(10))
+        .limit(3)
+        .build();
+
+    let start = Instant::now();
+    assert_eq!(batcher.call(1).await, 1);
+    assert_eq!(batcher.call_many(vec![1, 2, 3]).await, 3);
+    assert!(start.elapsed().as_millis() < 500);
+}
+
+#[tokio::test]
+async fn fire_and_forget() {
+    let processed = Arc::new(AtomicUsize::new(0));
+    let batcher = {
+        let processed = processed.clone();
+        Batcher::builder(move |numbers: Vec<u32>| {
+            let processed = processed.clone();
+            async move {
+                processed.fetch_add(numbers.len(), Ordering::SeqCst);
+            }
+        })
+        .window(Duration::from_millis(10))
+        .build()
+    };
+
+    batcher.fire_and_forget(vec![1, 2, 3]).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(processed.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn stored_in_struct() {
+    struct Service {
+        lengths: Batcher<String, Vec<usize>, Split>,
+        total: Batcher<u32, u32, Cloned>,
+    }
+
+    let service = Service {
+        lengths: Batcher::builder_split(async |values: Vec<String>| {
+            values.iter().map(String::len).collect::<Vec<_>>()
+        })
+        .build(),
+        total: Batcher::builder(async |values: Vec<u32>| values.iter().sum()).build(),
+    };
+
+    assert_eq!(service.lengths.call("abc".to_string()).await, 3);
+    assert_eq!(service.total.call_many(vec![1, 2]).await, 3);
+}
+
+#[tokio::test]
+async fn flush() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_secs(10))
+        .build();
+
+    let start = Instant::now();
+    let call = tokio::spawn({
+        let batcher = batcher.clone();
+        async move { batcher.call_many(vec![1, 2]).await }
+    });
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    batcher.flush().await;
+    assert_eq!(call.await.unwrap(), 2);
+    assert!(start.elapsed().as_millis() < 500);
+}
+
+#[tokio::test]
+async fn shutdown() {
+    let processed = Arc::new(AtomicUsize::new(0));
+    let batcher = {
+        let processed = processed.clone();
+        Batcher::builder(move |numbers: Vec<u32>| {
+            let processed = processed.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                processed.fetch_add(numbers.len(), Ordering::SeqCst);
+            }
+        })
+        .window(Duration::from_secs(10))
+        .build()
+    };
+
+    batcher.fire_and_forget(vec![1, 2, 3]).await;
+    batcher.shutdown().await;
+    assert_eq!(processed.load(Ordering::SeqCst), 3);
+    assert!(batcher.is_shut_down());
+
+    let call = tokio::spawn(async move { batcher.call(1).await });
+    assert!(call.await.unwrap_err().is_panic());
+}
+
+#[tokio::test]
+async fn cancel_on_drop() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers)
+        .window(Duration::from_millis(200))
+        .cancel_on_drop(true)
+        .build();
+
+    let cancelled = tokio::time::timeout(Duration::from_millis(50), batcher.call(1)).await;
+    assert!(cancelled.is_err());
+    assert_eq!(batcher.call(2).await, vec![2]);
+}
+
+#[tokio::test]
+async fn errors() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| {
+        if numbers.contains(&0) {
+            panic!("zero");
+        }
+        numbers.len()
+    })
+    .window(Duration::from_millis(200))
+    .queue(2)
+    .build();
+
+    let (panicked, full, ok) = tokio::join!(
+        batcher.try_call_many(vec![0, 1]),
+        batcher.try_call(2),
+        async {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            batcher.try_call(3).await
+        }
+    );
+    assert_eq!(panicked, Err(Error::InnerPanicked("zero".to_string())));
+    assert_eq!(full, Err(Error::QueueFull));
+    assert_eq!(ok, Ok(1));
+
+    batcher.config().update(|config| config.timeout = Some(Duration::from_millis(50)));
+    assert_eq!(batcher.try_call(4).await, Err(Error::Timeout));
+
+    batcher.shutdown().await;
+    assert_eq!(batcher.try_call(5).await, Err(Error::ExecutorClosed));
+    assert_eq!(batcher.try_fire_and_forget(vec![6]).await, Err(Error::ExecutorClosed));
+}
+
+#[tokio::test]
+async fn adaptive() {
+    let adaptive = AdaptiveWindow {
+        min: Duration::from_millis(1),
+        max: Duration::from_millis(100),
+        target: AdaptiveTarget::BatchSize(11),
+    };
+    let ms = Duration::from_millis;
+    assert_eq!(adaptive.window(None, None), ms(1));
+    assert_eq!(adaptive.window(Some(ms(2)), None), ms(20));
+    // The batch wouldn't fill within the maximum window, so callers don't wait
+    assert_eq!(adaptive.window(Some(ms(20)), None), ms(1));
+
+    let latency = AdaptiveWindow {
+        target: AdaptiveTarget::Latency(ms(50)),
+        ..adaptive
+    };
+    assert_eq!(latency.window(Some(ms(2)), Some(ms(20))), ms(30));
+    assert_eq!(latency.window(Some(ms(2)), Some(ms(80))), ms(1));
+    assert_eq!(latency.window(Some(ms(40)), Some(ms(20))), ms(1));
+
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_secs(10))
+        .adaptive(AdaptiveWindow {
+            max: Duration::from_millis(200),
+            ..adaptive
+        })
+        .build();
+
+    // Low traffic: calls don't wait for the (unused) 10s window nor the maximum
+    let start = Instant::now();
+    assert_eq!(batcher.call(1).await, 1);
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(batcher.call(2).await, 1);
+    assert!(start.elapsed() < Duration::from_millis(450));
+
+    // Burst: the window grows so batches fill up
+    let mut calls = vec![];
+    for number in 0..60 {
+        let batcher = batcher.clone();
+        calls.push(tokio::spawn(async move { batcher.call(number).await }));
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    let mut sizes = vec![];
+    for call in calls {
+        sizes.push(call.await.unwrap());
+    }
+    assert!(sizes.iter().any(|size| *size >= 5), "{sizes:?}");
+    assert!(batcher.stats().batches < 40);
+}
+
+#[tokio::test]
+async fn weight() {
+    let weights = Arc::new(std::sync::Mutex::new(vec![]));
+    let batcher = {
+        let weights = weights.clone();
+        Batcher::builder_split(move |values: Vec<String>| {
+            let weight = values.iter().map(String::len).sum::<usize>();
+            weights.lock().unwrap().push(weight);
+            async move { values.iter().map(String::len).collect::<Vec<_>>() }
+        })
+        .window(Duration::from_millis(50))
+        .weight(|value| value.len() as u64)
+        .max_weight(10)
+        .build()
+    };
+
+    let values = |lengths: &[usize]| lengths.iter().map(|len| "x".repeat(*len)).collect();
+    let (a, b, c) = tokio::join!(
+        batcher.call_many(values(&[4, 4, 4])),
+        batcher.call("xx".to_string()),
+        batcher.call_many(values(&[12, 1])),
+    );
+    assert_eq!(a, vec![4, 4, 4]);
+    assert_eq!(b, 2);
+    assert_eq!(c, vec![12, 1]);
+
+    // Batches start when the next call would exceed the maximum weight, a single item heavier
+    // than the maximum runs on its own
+    let mut weights = weights.lock().unwrap().clone();
+    weights.sort();
+    assert_eq!(weights, vec![1, 6, 8, 12]);
+
+    // The weight of a call saturates instead of overflowing
+    let batcher = Batcher::builder(async |weights: Vec<u64>| weights.len())
+        .window(Duration::from_millis(10))
+        .weight(|weight| *weight)
+        .max_weight(10)
+        .build();
+    assert_eq!(batcher.call_many(vec![u64::MAX, 1]).await, 2);
+}
+
+#[tokio::test]
+async fn limit_chunks() {
+    let sizes = Arc::new(std::sync::Mutex::new(vec![]));
+    let batcher = {
+        let sizes = sizes.clone();
+        Batcher::builder_split(move |numbers: Vec<u32>| {
+            sizes.lock().unwrap().push(numbers.len());
+            async move {
+                // Later chunks complete first, results are still reassembled in order
+                let delay = 50u64.saturating_sub(numbers[0] as u64 * 5);
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                numbers.into_iter().map(|n| n * 2).collect::<Vec<_>>()
+            }
+        })
+        .window(Duration::from_millis(10))
+        .limit(3)
+        .build()
+    };
+
+    let start = Instant::now();
+    let result = batcher.call_many((0..8).collect()).await;
+    assert_eq!(result, vec![0, 2, 4, 6, 8, 10, 12, 14]);
+    assert_eq!(*sizes.lock().unwrap(), vec![3, 3, 2]);
+    // The chunks ran concurrently
+    assert!(start.elapsed() < Duration::from_millis(120));
+
+    // Results of a single shared value can't be merged, such calls aren't split
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_millis(10))
+        .limit(2)
+        .build();
+    let (a, b) = tokio::join!(batcher.call(1), batcher.call_many(vec![1, 2, 3]));
+    assert_eq!((a, b), (1, 3));
+
+    // A call whose chunks don't all fit in the queue is rejected before any chunk runs
+    let ran = Arc::new(AtomicUsize::new(0));
+    let batcher = {
+        let ran = ran.clone();
+        Batcher::builder_split(move |numbers: Vec<u32>| {
+            ran.fetch_add(1, Ordering::SeqCst);
+            async move { numbers }
+        })
+        .window(Duration::from_millis(10))
+        .limit(2)
+        .queue(4)
+        .build()
+    };
+    let full = batcher.try_call_many(vec![1, 2, 3, 4, 5]).await;
+    assert_eq!(full, Err(Error::QueueFull));
+    assert_eq!(batcher.stats().pending_items, 0);
+    assert_eq!(batcher.try_call_many(vec![1, 2, 3, 4]).await, Ok(vec![1, 2, 3, 4]));
+    assert_eq!(ran.load(Ordering::SeqCst), 2);
+    let full = batcher.try_fire_and_forget(vec![1, 2, 3, 4, 5]).await;
+    assert_eq!(full, Err(Error::QueueFull));
+    batcher.flush().await;
+    assert_eq!(ran.load(Ordering::SeqCst), 2);
+
+    // A limit (or maximum weight) of 0 runs every call in a batch of its own
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_millis(10))
+        .limit(0)
+        .build();
+    let (a, b) = tokio::join!(batcher.call(1), batcher.call_many(vec![1, 2]));
+    assert_eq!((a, b), (1, 2));
+    batcher.config().update(|config| {
+        config.limit = Some(1000);
+        config.max_weight = Some(0);
+    });
+    assert_eq!(batcher.call_many(vec![1, 2]).await, 2);
+}
+
+#[tokio::test]
+async fn min_and_max_wait() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_millis(10))
+        .min(3)
+        .max_wait(Duration::from_millis(200))
+        .build();
+
+    // Below the minimum, the batch waits past the window until max_wait
+    let start = Instant::now();
+    assert_eq!(batcher.call(1).await, 1);
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    let start = Instant::now();
+    let (a, _, _) = tokio::join!(batcher.call(1), batcher.call(2), batcher.call(3));
+    assert_eq!(a, 3);
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    // Without max_wait, only a flush starts a batch below the minimum
+    batcher.config().update(|config| config.max_wait = None);
+    let (a, _) = tokio::join!(batcher.call(1), async {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        batcher.flush().await;
+    });
+    assert_eq!(a, 1);
+}
+
+#[tokio::test]
+async fn rate() {
+    assert_eq!("10/s".parse(), Ok(Rate::per_second(10)));
+    let rate: Rate = "5/100ms".parse().unwrap();
+    assert_eq!(rate.interval(), Duration::from_millis(20));
+    assert!("10".parse::<Rate>().is_err());
+    assert!("0/s".parse::<Rate>().is_err());
+
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_millis(1))
+        .rate(Rate::per_second(10))
+        .burst(2)
+        .build();
+
+    // The burst starts right away, the next batch waits for the rate
+    let start = Instant::now();
+    batcher.call(1).await;
+    batcher.call(2).await;
+    assert!(start.elapsed() < Duration::from_millis(50));
+    batcher.call(3).await;
+    assert!(start.elapsed() >= Duration::from_millis(80));
+
+    // While throttled, items accumulate into larger batches instead of being rejected
+    let mut calls = vec![];
+    for number in 0..20 {
+        let batcher = batcher.clone();
+        calls.push(tokio::spawn(async move { batcher.call(number).await }));
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    for call in calls {
+        call.await.unwrap();
+    }
+    let stats = batcher.stats();
+    assert_eq!(stats.items, 23);
+    assert!(stats.batches <= 3 + 4, "{stats:?}");
+}
+
+#[tokio::test]
+async fn priority() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.iter().sum::<u32>())
+        .window(Duration::from_secs(10))
+        .build();
+
+    // The urgent call starts the pending batch right away
+    let start = Instant::now();
+    let (a, b) = tokio::join!(
+        batcher.call(1),
+        batcher.call_with_priority(2, Priority::Urgent)
+    );
+    assert_eq!((a, b), (3, 3));
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(batcher.stats().flushes.urgent, 1);
+}
+
+#[tokio::test]
+async fn dedupe() {
+    let received = Arc::new(std::sync::Mutex::new(vec![]));
+    let batcher = {
+        let received = received.clone();
+        Batcher::builder_split(move |ids: Vec<u32>| {
+            received.lock().unwrap().push(ids.clone());
+            async move { ids.into_iter().map(|id| id * 10).collect::<Vec<_>>() }
+        })
+        .window(Duration::from_millis(50))
+        .dedupe()
+        .build()
+    };
+
+    let (a, b, c) = tokio::join!(
+        batcher.call(42),
+        batcher.call_many(vec![7, 42, 7]),
+        batcher.call(42)
+    );
+    assert_eq!((a, b, c), (420, vec![70, 420, 70], 420));
+    assert_eq!(*received.lock().unwrap(), vec![vec![42, 7]]);
+
+    let batcher = Batcher::builder(async |ids: Vec<u32>| ids.len())
+        .window(Duration::from_millis(50))
+        .dedupe()
+        .build();
+    assert_eq!(tokio::join!(batcher.call(1), batcher.call(1)), (1, 1));
+
+    // Missing results fail every caller instead of misaligning their results
+    let batcher = Batcher::builder_split(async |ids: Vec<u32>| ids[1..].to_vec())
+        .window(Duration::from_millis(50))
+        .dedupe()
+        .build();
+    let (a, b) = tokio::join!(batcher.try_call(1), batcher.try_call_many(vec![1, 2]));
+    let missing = Error::MissingResults {
+        expected: 3,
+        returned: 1,
+    };
+    assert_eq!(a, Err(missing.clone()));
+    assert_eq!(b, Err(missing));
+}
+
+#[tokio::test]
+async fn cache() {
+    let cache = Cache::new(Duration::from_millis(50), 2);
+    cache.insert(1, "a");
+    cache.insert(2, "b");
+    cache.insert(3, "c");
+    assert_eq!(cache.get(&1), None);
+    assert_eq!(cache.get(&3), Some("c"));
+
+    let loaded = cache
+        .get_or_load_many(vec![2, 3, 4], |id| *id, async |ids| {
+            assert_eq!(ids, [4]);
+            vec!["d"]
+        })
+        .await;
+    assert_eq!(loaded, Ok(vec!["b", "c", "d"]));
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(cache.get(&4), None);
+
+    // Invalidated keys no longer count towards the capacity
+    let cache = Cache::new(Duration::from_secs(10), 2);
+    cache.insert(1, "a");
+    cache.insert(2, "b");
+    cache.invalidate(&1);
+    cache.insert(3, "c");
+    assert_eq!((cache.get(&2), cache.get(&3)), (Some("b"), Some("c")));
+
+    // Loads in flight while the cache is invalidated aren't cached
+    let loaded = cache.get_or_load(4, async {
+        cache.invalidate(&4);
+        "stale"
+    });
+    assert_eq!(loaded.await, "stale");
+    assert_eq!(cache.get(&4), None);
+
+    // Loaders returning fewer values than items fail
+    let loaded = cache.get_or_load_many(vec![5, 6], |id| *id, async |_| vec!["e"]);
+    let missing = Error::MissingResults {
+        expected: 2,
+        returned: 1,
+    };
+    assert_eq!(loaded.await, Err(missing));
+    assert_eq!(cache.get(&5), None);
+}
+
+#[test]
+fn multiple_runtimes() {
+    let batcher = Batcher::builder(|numbers: Vec<u32>| async move { numbers.iter().sum::<u32>() })
+        .window(Duration::from_millis(10))
+        .build();
+
+    for _ in 0..3 {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let sum = runtime.block_on(async {
+            let (a, b) = tokio::join!(batcher.call(1), batcher.call(2));
+            a + b
+        });
+        assert_eq!(sum, 6);
+    }
+
+    // Calls pending when their runtime shuts down no longer count as queued
+    let batcher = Batcher::builder(|numbers: Vec<u32>| async move { numbers.iter().sum::<u32>() })
+        .window(Duration::from_secs(10))
+        .build();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    runtime.spawn({
+        let batcher = batcher.clone();
+        async move { batcher.call(1).await }
+    });
+    runtime.block_on(async { tokio::time::sleep(Duration::from_millis(20)).await });
+    assert_eq!(batcher.stats().pending_items, 1);
+    drop(runtime);
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    assert_eq!(batcher.stats().pending_items, 0);
+    assert_eq!(batcher.stats().pending_calls, 0);
+    let urgent = batcher.call_with_priority(2, Priority::Urgent);
+    assert_eq!(runtime.block_on(urgent), 2);
+}
+
+#[test]
+fn blocking() {
+    let batcher = Batcher::builder(|numbers: Vec<u32>| async move { numbers.iter().sum::<u32>() })
+        .window(Duration::from_millis(10))
+        .build();
+
+    let threads: Vec<_> = (1..=3)
+        .map(|number| {
+            let batcher = batcher.clone();
+            std::thread::spawn(move || batched::blocking::block_on(batcher.call(number)))
+        })
+        .collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 6);
+    }
+}
+
+#[tokio::test]
+async fn map_evicts_idle_batchers() {
+    let map = BatcherMap::new();
+    let build = || {
+        Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+            .window(Duration::from_millis(1))
+            .build()
+    };
+
+    for key in 0..200 {
+        assert_eq!(map.get_or_insert_with(key, build).call(1).await, 1);
+    }
+    assert!(map.len() < 20);
+    assert_eq!(map.stats().batches, 200);
+
+    // Batchers still in use are kept
+    let batcher = map.get_or_insert_with(200, build);
+    for key in 201..400 {
+        map.get_or_insert_with(key, build);
+    }
+    assert_eq!(batcher.call(1).await, 1);
+    assert_eq!(map.get_or_insert_with(200, build).stats().batches, 1);
+}
+
+#[tokio::test]
+async fn dropped_callers_release_the_queue() {
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        numbers.len()
+    })
+    .window(Duration::from_millis(1))
+    .limit(1)
+    .concurrent(1)
+    .queue(5)
+    .timeout(Duration::from_millis(50))
+    .build();
+
+    // Callers time out while waiting for the collector, which is blocked on the concurrency limit
+    let calls: Vec<_> = (0..5)
+        .map(|number| {
+            let batcher = batcher.clone();
+            tokio::spawn(async move { batcher.try_call(number).await })
+        })
+        .collect();
+    for call in calls {
+        assert_eq!(call.await.unwrap(), Err(Error::Timeout));
+    }
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let stats = batcher.stats();
+    assert_eq!((stats.pending_items, stats.pending_calls), (0, 0));
+    batcher.config().update(|config| config.timeout = None);
+    assert_eq!(batcher.try_call_many(vec![1, 2, 3, 4, 5]).await, Ok(5));
+
+    // Unsent chunks of a call are released as well
+    let batcher = Batcher::builder_split(async |numbers: Vec<u32>| {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        numbers
+    })
+    .window(Duration::from_millis(1))
+    .limit(1)
+    .concurrent(1)
+    .timeout(Duration::from_millis(50))
+    .build();
+    let chunked = batcher.try_call_many((0..8).collect()).await;
+    assert_eq!(chunked, Err(Error::Timeout));
+
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let stats = batcher.stats();
+    assert_eq!((stats.pending_items, stats.pending_calls), (0, 0));
+}
@@ -1,6 +1,6 @@
-use std::{sync::{atomic::AtomicBool, LazyLock}, time::{Duration, Instant}};
+use std::{sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, LazyLock}, time::{Duration, Instant}};
 
-use batched::{batched, error::SharedError};
+use batched::{batched, config::AdaptiveTarget, error::SharedError};
 
 #[tokio::test]
 async fn simple() {
@@ -19,6 +19,7 @@ async fn simple() {
 }
 
 #[tokio::test]
+#[allow(clippy::useless_conversion)]
 async fn propagates_errors() {
     #[batched(window = 100, limit = 1000)]
     fn error(_a: Vec<()>) -> Result<(), SharedError<std::io::Error>> {
@@ -41,6 +42,7 @@ async fn empty_batch() {
 }
 
 #[tokio::test]
+#[allow(clippy::bool_assert_comparison)]
 async fn asynchronous() {
     static BACKGROUND_FN_RAN: LazyLock<AtomicBool> = LazyLock::new(|| 
         AtomicBool::new(false)
@@ -120,4 +122,605 @@ async fn returned_iterator_with_error() {
 
     let result = add_each(2).await.unwrap();
     assert!(result == 3);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn method() {
+    #[derive(Clone)]
+    struct Counter {
+        offset: u32,
+    }
+
+    impl Counter {
+        #[batched(window = 100, limit = 1000, instance = self.offset)]
+        fn add(&self, numbers: Vec<u32>) -> u32 {
+            numbers.iter().sum::<u32>() + self.offset
+        }
+    }
+
+    let a = Counter { offset: 0 };
+    let b = Counter { offset: 1000 };
+
+    let (a_total, b_total) = tokio::join!(a.add_multiple(vec![1, 1]), b.add_multiple(vec![1, 1, 1]));
+    assert_eq!(a_total, 2);
+    assert_eq!(b_total, 1003);
+}
+
+#[tokio::test]
+async fn method_instance_key() {
+    #[derive(Clone)]
+    struct Shard {
+        id: u32,
+    }
+
+    impl Shard {
+        #[batched(window = 100, limit = 1000, instance = self.id)]
+        fn count(&self, items: Vec<u32>) -> usize {
+            items.len()
+        }
+    }
+
+    let a = Shard { id: 1 };
+    let b = a.clone();
+    let c = Shard { id: 2 };
+
+    let (a_count, b_count, c_count) = tokio::join!(a.count(1), b.count(2), c.count(3));
+    assert_eq!(a_count, 2);
+    assert_eq!(b_count, 2);
+    assert_eq!(c_count, 1);
+}
+
+#[tokio::test]
+async fn method_instance_reused_address() {
+    #[derive(Clone)]
+    struct Db {
+        name: String,
+    }
+
+    impl Db {
+        #[batched(window = 10, instance = self.name.clone())]
+        fn query(&self, ids: Vec<u32>) -> Vec<String> {
+            ids.iter().map(|id| format!("{} {id}", self.name)).collect()
+        }
+    }
+
+    // Instances freed in between may be allocated at the same address
+    for name in ["pool-A", "pool-B", "pool-C"] {
+        let db = Box::new(Db { name: name.to_string() });
+        assert_eq!(db.query(1).await, format!("{name} 1"));
+    }
+}
+
+#[tokio::test]
+async fn partition_key() {
+    #[batched(window = 100, limit = 1000)]
+    fn insert(tenant: u32, rows: Vec<u32>) -> Vec<(u32, usize)> {
+        rows.iter().map(|_| (tenant, rows.len())).collect()
+    }
+
+    let (a, b, c) = tokio::join!(insert(1, 10), insert(2, 20), insert_multiple(1, vec![11, 12]));
+    assert_eq!(a, (1, 3));
+    assert_eq!(b, (2, 1));
+    assert_eq!(c, vec![(1, 3), (1, 3)]);
+}
+
+#[tokio::test]
+async fn method_partition_key() {
+    #[derive(Clone)]
+    struct Table;
+
+    impl Table {
+        #[batched(window = 100, limit = 2, instance = ())]
+        fn insert(&self, rows: Vec<u32>, shard: String) -> usize {
+            assert!(rows.iter().all(|row| row.to_string().starts_with(&shard)));
+            rows.len()
+        }
+    }
+
+    let table = Table;
+    let (a, b, c) = tokio::join!(
+        table.insert(10, "1".to_string()),
+        table.insert(20, "2".to_string()),
+        table.insert(11, "1".to_string())
+    );
+    assert_eq!((a, b, c), (2, 1, 2));
+}
+
+#[tokio::test]
+async fn non_literal_attributes() {
+    struct Config {
+        window_ms: u64,
+    }
+
+    const CONFIG: Config = Config { window_ms: 300 };
+    const LIMIT: usize = 3;
+
+    #[batched(window = CONFIG.window_ms, window1 = "10ms", limit = LIMIT)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    let start = Instant::now();
+    add(1).await;
+    assert!(start.elapsed().as_millis() < 100);
+
+    let start = Instant::now();
+    add_multiple(vec![1, 1, 1]).await;
+    assert!(start.elapsed().as_millis() < 100);
+
+    let start = Instant::now();
+    add_multiple(vec![1, 1]).await;
+    assert!(start.elapsed().as_millis() >= 300);
+}
+
+#[tokio::test]
+async fn runtime_config() {
+    #[batched(window = 1000, limit = 1000)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    add_config().update(|config| config.window = Duration::from_millis(10));
+    let start = Instant::now();
+    add(1).await;
+    assert!(start.elapsed().as_millis() < 500);
+
+    add_config().update(|config| {
+        config.window = Duration::from_secs(10);
+        config.limit = Some(2);
+    });
+    let start = Instant::now();
+    let total = add_multiple(vec![1, 1]).await;
+    assert_eq!(total, 2);
+    assert!(start.elapsed().as_millis() < 500);
+
+    let config = add_config().load();
+    assert_eq!(config.limit, Some(2));
+    assert_eq!(config.window, Duration::from_secs(10));
+}
+
+#[tokio::test]
+async fn runtime_config_concurrency() {
+    static RUNNING: AtomicUsize = AtomicUsize::new(0);
+    static MAX_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 1, limit = 1)]
+    async fn work(_items: Vec<()>) {
+        let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+        MAX_RUNNING.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    work_config().update(|config| config.concurrent = Some(2));
+    let calls: Vec<_> = (0..8).map(|_| tokio::task::spawn(work(()))).collect();
+    for call in calls {
+        call.await.unwrap();
+    }
+    assert_eq!(MAX_RUNNING.load(Ordering::SeqCst), 2);
+
+    MAX_RUNNING.store(0, Ordering::SeqCst);
+    work_config().update(|config| config.concurrent = Some(1));
+    let calls: Vec<_> = (0..4).map(|_| tokio::task::spawn(work(()))).collect();
+    for call in calls {
+        call.await.unwrap();
+    }
+    assert_eq!(MAX_RUNNING.load(Ordering::SeqCst), 1);
+
+    // 0 runs one batch at a time instead of none
+    MAX_RUNNING.store(0, Ordering::SeqCst);
+    work_config().update(|config| config.concurrent = Some(0));
+    let calls: Vec<_> = (0..4).map(|_| tokio::task::spawn(work(()))).collect();
+    for call in calls {
+        call.await.unwrap();
+    }
+    assert_eq!(MAX_RUNNING.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn flush_and_shutdown() {
+    #[batched(window = 10000)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    #[batched(window = 10000)]
+    fn count(tenant: String, numbers: Vec<u32>) -> usize {
+        let _ = tenant;
+        numbers.len()
+    }
+
+    let start = Instant::now();
+    let total = tokio::spawn(add_multiple(vec![1, 2]));
+    let counted = tokio::spawn(count("a".to_string(), 1));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    flush_add().await;
+    shutdown_count().await;
+    assert_eq!(total.await.unwrap(), 3);
+    assert_eq!(counted.await.unwrap(), 1);
+    assert!(start.elapsed().as_millis() < 1000);
+
+    let rejected = tokio::spawn(count("b".to_string(), 1));
+    assert!(rejected.await.unwrap_err().is_panic());
+}
+
+#[tokio::test]
+async fn cancel_on_drop() {
+    static PROCESSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 200, cancel_on_drop)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        PROCESSED.fetch_add(numbers.len(), Ordering::SeqCst);
+        numbers.iter().sum()
+    }
+
+    let cancelled = tokio::time::timeout(Duration::from_millis(50), add_multiple(vec![1, 2])).await;
+    assert!(cancelled.is_err());
+
+    assert_eq!(add(3).await, 3);
+    assert_eq!(PROCESSED.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn fallible() {
+    #[batched(window = 1000, timeout = 50, cancel_on_drop, fallible)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    #[batched(window = 10, fallible)]
+    fn double(numbers: Vec<u32>) -> Result<Vec<u32>, SharedError<anyhow::Error>> {
+        Ok(numbers.into_iter().map(|n| n * 2).collect())
+    }
+
+    assert_eq!(add(1).await, Err(batched::Error::Timeout));
+    add_config().update(|config| config.window = Duration::from_millis(10));
+    assert_eq!(add_multiple(vec![1, 2]).await, Ok(3));
+
+    assert_eq!(double(2).await.unwrap(), 4);
+    shutdown_double().await;
+    let error = double_multiple(vec![1, 2]).await.unwrap_err();
+    assert_eq!(error.downcast_ref::<batched::Error>(), Some(&batched::Error::ExecutorClosed));
+}
+
+#[tokio::test]
+async fn panic_isolation() {
+    #[batched(window = 50, fallible)]
+    fn divide(numbers: Vec<u32>) -> Vec<u32> {
+        numbers.into_iter().map(|n| 100 / n).collect()
+    }
+
+    let (a, b) = tokio::join!(divide(0), divide(1));
+    let error = batched::Error::InnerPanicked("attempt to divide by zero".to_string());
+    assert_eq!(a, Err(error.clone()));
+    assert_eq!(b, Err(error));
+
+    assert_eq!(divide_multiple(vec![1, 2]).await, Ok(vec![100, 50]));
+}
+
+#[tokio::test]
+async fn retry() {
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn is_transient(error: &std::io::Error) -> bool {
+        error.kind() == std::io::ErrorKind::Interrupted
+    }
+
+    #[batched(window = 10, retry(attempts = 3, backoff = "constant", base = 10, predicate = is_transient))]
+    fn insert(rows: Vec<u32>) -> Result<Vec<u32>, SharedError<std::io::Error>> {
+        let kind = match (rows[0], ATTEMPTS.fetch_add(1, Ordering::SeqCst)) {
+            (0, _) => std::io::ErrorKind::InvalidInput,
+            (_, 0 | 1) => std::io::ErrorKind::Interrupted,
+            _ => return Ok(rows),
+        };
+        Err(std::io::Error::from(kind))
+    }
+
+    assert_eq!(insert_multiple(vec![1, 2]).await.unwrap(), vec![1, 2]);
+    assert_eq!(ATTEMPTS.swap(0, Ordering::SeqCst), 3);
+
+    assert!(insert(0).await.is_err());
+    assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn split_on_error() {
+    static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 50, split_on_error)]
+    fn insert(rows: Vec<i32>) -> Result<Vec<i32>, SharedError<std::io::Error>> {
+        RUNS.fetch_add(1, Ordering::SeqCst);
+        if rows.iter().any(|row| *row < 0) {
+            return Err(std::io::Error::other("negative row"));
+        }
+        Ok(rows)
+    }
+
+    let (a, b, c, d) = tokio::join!(
+        insert(1),
+        insert_multiple(vec![2, 3]),
+        insert(-1),
+        insert(4)
+    );
+    assert_eq!(a.unwrap(), 1);
+    assert_eq!(b.unwrap(), vec![2, 3]);
+    assert!(c.is_err());
+    assert_eq!(d.unwrap(), 4);
+
+    // [1, 2 3, -1, 4] -> [1, 2 3] + [-1, 4] -> [-1] + [4]
+    assert_eq!(RUNS.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn stats() {
+    #[batched(window = 50, limit = 3)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        numbers.iter().sum()
+    }
+
+    let (_, _, stats) = tokio::join!(add_multiple(vec![1, 2, 3]), add(4), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        add_stats()
+    });
+    assert_eq!(stats.pending_items, 1);
+    assert_eq!(stats.pending_calls, 1);
+    assert_eq!(stats.inflight_batches, 1);
+
+    let stats = add_stats();
+    assert_eq!((stats.batches, stats.items), (2, 4));
+    assert_eq!((stats.flushes.limit, stats.flushes.window), (1, 1));
+    assert_eq!((stats.batch_size_p50, stats.batch_size_p99), (1, 3));
+    assert!(stats.wait_p99 >= Duration::from_millis(40));
+    assert_eq!((stats.pending_items, stats.inflight_batches), (0, 0));
+}
+
+#[tokio::test]
+async fn adaptive() {
+    #[batched(adaptive(min = 1, max = "200ms", target_size = 8), limit = 100)]
+    fn count(numbers: Vec<u32>) -> usize {
+        numbers.len()
+    }
+
+    let config = count_config().load();
+    let adaptive = config.adaptive.unwrap();
+    assert_eq!(config.window, Duration::from_millis(1));
+    assert_eq!(adaptive.max, Duration::from_millis(200));
+    assert_eq!(adaptive.target, AdaptiveTarget::BatchSize(8));
+
+    let start = Instant::now();
+    assert_eq!(count(1).await, 1);
+    assert!(start.elapsed() < Duration::from_millis(100));
+
+    #[batched(window = 5, adaptive(max = 100, target_latency = "50ms"))]
+    fn latency(numbers: Vec<u32>) -> usize {
+        numbers.len()
+    }
+
+    let adaptive = latency_config().load().adaptive.unwrap();
+    assert_eq!(adaptive.min, Duration::ZERO);
+    assert_eq!(adaptive.target, AdaptiveTarget::Latency(Duration::from_millis(50)));
+    assert_eq!(latency(1).await, 1);
+}
+
+#[tokio::test]
+async fn weight() {
+    static WEIGHTS: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(vec![]);
+
+    fn weight(value: &str) -> u64 {
+        value.len() as u64
+    }
+
+    #[batched(window = 20, weight = weight, max_weight = 10)]
+    fn lengths(values: Vec<String>) -> Vec<usize> {
+        WEIGHTS.lock().unwrap().push(values.iter().map(String::len).sum());
+        values.iter().map(String::len).collect()
+    }
+
+    let lengths = lengths_multiple(vec!["xxxx".to_string(); 5]).await;
+    assert_eq!(lengths, vec![4; 5]);
+    assert_eq!(*WEIGHTS.lock().unwrap(), vec![8, 8, 4]);
+}
+
+#[tokio::test]
+async fn limit_chunks() {
+    static SIZES: std::sync::Mutex<Vec<usize>> = std::sync::Mutex::new(vec![]);
+
+    #[batched(window = 10, limit = 2)]
+    fn double(numbers: Vec<u32>) -> Vec<u32> {
+        SIZES.lock().unwrap().push(numbers.len());
+        numbers.into_iter().map(|n| n * 2).collect()
+    }
+
+    let (a, b) = tokio::join!(double_multiple(vec![1, 2, 3, 4, 5]), double(6));
+    assert_eq!(a, vec![2, 4, 6, 8, 10]);
+    assert_eq!(b, 12);
+    assert!(SIZES.lock().unwrap().iter().all(|size| *size <= 2));
+}
+
+#[tokio::test]
+async fn min_and_max_wait() {
+    #[batched(window = 10, min = 2, max_wait = "150ms")]
+    fn count(numbers: Vec<u32>) -> usize {
+        numbers.len()
+    }
+
+    let start = Instant::now();
+    assert_eq!(count(1).await, 1);
+    assert!(start.elapsed() >= Duration::from_millis(150));
+
+    let config = count_config().load();
+    assert_eq!((config.min, config.max_wait), (Some(2), Some(Duration::from_millis(150))));
+    assert_eq!(tokio::join!(count(1), count(2)), (2, 2));
+}
+
+#[tokio::test]
+async fn rate() {
+    #[batched(window = 1, rate = "20/s", burst = 1)]
+    fn count(numbers: Vec<u32>) -> usize {
+        numbers.len()
+    }
+
+    let config = count_config().load();
+    assert_eq!(config.rate.unwrap().interval(), Duration::from_millis(50));
+    assert_eq!(config.burst, Some(1));
+
+    let start = Instant::now();
+    count(1).await;
+    count(2).await;
+    assert!(start.elapsed() >= Duration::from_millis(40));
+}
+
+#[tokio::test]
+async fn priority() {
+    static PROCESSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 10000)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    #[batched(window = 10000, asynchronous)]
+    fn record(numbers: Vec<u32>) {
+        PROCESSED.fetch_add(numbers.len(), Ordering::SeqCst);
+    }
+
+    let start = Instant::now();
+    let (a, b) = tokio::join!(add(1), add_urgent(2));
+    assert_eq!((a, b), (3, 3));
+    let (a, b) = tokio::join!(add(3), add_with_priority(4, batched::Priority::Urgent));
+    assert_eq!((a, b), (7, 7));
+    assert_eq!(add_stats().flushes.urgent, 2);
+
+    record(1).await;
+    record_urgent(2).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(PROCESSED.load(Ordering::SeqCst), 2);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn dedupe() {
+    static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 50, dedupe)]
+    fn load(ids: Vec<u32>) -> Result<Vec<String>, String> {
+        RECEIVED.fetch_add(ids.len(), Ordering::SeqCst);
+        Ok(ids.iter().map(|id| format!("user {id}")).collect())
+    }
+
+    let calls: Vec<_> = (0..100).map(|_| tokio::spawn(load(42))).collect();
+    for call in calls {
+        assert_eq!(call.await.unwrap().as_deref(), Ok("user 42"));
+    }
+    assert_eq!(RECEIVED.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn cache() {
+    static RECEIVED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 10, cache(ttl = "1s", capacity = 100))]
+    fn load(ids: Vec<u32>) -> Vec<String> {
+        RECEIVED.fetch_add(ids.len(), Ordering::SeqCst);
+        ids.iter().map(|id| format!("user {id}")).collect()
+    }
+
+    assert_eq!(load(1).await, "user 1");
+    assert_eq!(load(1).await, "user 1");
+    assert_eq!(RECEIVED.load(Ordering::SeqCst), 1);
+
+    assert_eq!(load_multiple(vec![1, 2, 3]).await, ["user 1", "user 2", "user 3"]);
+    assert_eq!(RECEIVED.load(Ordering::SeqCst), 3);
+    assert_eq!(load_stats().batches, 2);
+
+    invalidate_load(2);
+    assert_eq!(load(2).await, "user 2");
+    assert_eq!(RECEIVED.load(Ordering::SeqCst), 4);
+
+    clear_load();
+    assert_eq!(load_multiple(vec![1, 3]).await, ["user 1", "user 3"]);
+    assert_eq!(RECEIVED.load(Ordering::SeqCst), 6);
+
+    #[batched(window = 10, cache(ttl = "1s"))]
+    fn parse(inputs: Vec<String>) -> Result<Vec<u32>, String> {
+        inputs.iter().map(|input| input.parse().map_err(|_| format!("invalid {input}"))).collect()
+    }
+
+    assert_eq!(parse("1".to_string()).await, Ok(1));
+    assert_eq!(parse("x".to_string()).await, Err("invalid x".to_string()));
+    assert_eq!(parse_multiple(vec!["1".to_string(), "2".to_string()]).await, Ok(vec![1, 2]));
+
+    #[batched(window = 10, fallible, cache(ttl = "1s"))]
+    fn lookup(ids: Vec<u32>) -> Vec<u32> {
+        ids.into_iter().filter(|id| *id != 0).collect()
+    }
+
+    let missing = batched::Error::MissingResults { expected: 2, returned: 1 };
+    assert_eq!(lookup_multiple(vec![0, 1]).await, Err(missing));
+}
+
+#[test]
+fn multiple_runtimes() {
+    #[batched(window = 10)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    for _ in 0..3 {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (a, b) = runtime.block_on(async { tokio::join!(add(1), add(2)) });
+        assert_eq!((a, b), (3, 3));
+    }
+}
+
+#[test]
+fn blocking() {
+    static RECORDED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 50)]
+    fn add(numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+
+    #[batched(window = 10, fallible)]
+    fn double(numbers: Vec<u32>) -> Vec<u32> {
+        numbers.iter().map(|number| number * 2).collect()
+    }
+
+    #[batched(window = 10, asynchronous)]
+    fn record(numbers: Vec<u32>) {
+        RECORDED.fetch_add(numbers.len(), Ordering::SeqCst);
+    }
+
+    #[derive(Clone)]
+    struct Table;
+
+    impl Table {
+        #[batched(window = 10, instance = ())]
+        fn insert(&self, rows: Vec<u32>, shard: String) -> usize {
+            assert!(rows.iter().all(|row| row.to_string().starts_with(&shard)));
+            rows.len()
+        }
+    }
+
+    // Without a runtime, calls from several threads share a batch
+    let threads: Vec<_> = (1..=4).map(|number| std::thread::spawn(move || add_blocking(number))).collect();
+    for thread in threads {
+        assert_eq!(thread.join().unwrap(), 10);
+    }
+    assert_eq!(add_multiple_blocking(vec![1, 2]), 3);
+    assert_eq!(double_blocking(2), Ok(4));
+    assert_eq!(double_multiple_blocking(vec![1, 2]), Ok(vec![2, 4]));
+    record_multiple_blocking(vec![1, 2]);
+    assert_eq!(Table.insert_blocking(10, "1".to_string()), 1);
+
+    // Within a runtime, from a blocking task
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let total = runtime.block_on(async { tokio::task::spawn_blocking(|| add_blocking(5)).await.unwrap() });
+    assert_eq!(total, 5);
+
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(RECORDED.load(Ordering::SeqCst), 2);
+}
@@ -0,0 +1,87 @@
+use std::time::Duration;
+
+use batched::Batcher;
+use metrics_util::{
+    CompositeKey,
+    debugging::{DebugValue, DebuggingRecorder},
+};
+
+type Snapshot = Vec<(
+    CompositeKey,
+    Option<metrics::Unit>,
+    Option<metrics::SharedString>,
+    DebugValue,
+)>;
+
+/// Value of the metric `name` with exactly the given labels
+fn metric<'a>(
+    snapshot: &'a Snapshot,
+    name: &str,
+    labels: &[(&str, &str)],
+) -> Option<&'a DebugValue> {
+    snapshot
+        .iter()
+        .find(|(key, _, _, _)| {
+            let key = key.key();
+            let key_labels: Vec<_> = key.labels().map(|l| (l.key(), l.value())).collect();
+            key.name() == name && key_labels == labels
+        })
+        .map(|(_, _, _, value)| value)
+}
+
+#[test]
+fn metrics() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+
+    // The recorder is local to this thread, so the batcher runs on a current thread runtime
+    metrics::with_local_recorder(&recorder, || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+                .name("count")
+                .window(Duration::from_millis(50))
+                .limit(2)
+                .build();
+
+            assert_eq!(batcher.call_many(vec![1, 2]).await, 2);
+            assert_eq!(batcher.call(3).await, 1);
+        });
+    });
+
+    // Taking a snapshot drains the histograms
+    let snapshot = snapshotter.snapshot().into_vec();
+    let batches = |reason| {
+        metric(
+            &snapshot,
+            "batched_batches_total",
+            &[("name", "count"), ("reason", reason)],
+        )
+    };
+    assert!(matches!(batches("limit"), Some(DebugValue::Counter(1))));
+    assert!(matches!(batches("window"), Some(DebugValue::Counter(1))));
+
+    let Some(DebugValue::Histogram(sizes)) =
+        metric(&snapshot, "batched_batch_size", &[("name", "count")])
+    else {
+        panic!("missing batched_batch_size");
+    };
+    let sizes: Vec<f64> = sizes.iter().map(|size| size.into_inner()).collect();
+    assert_eq!(sizes, vec![2.0, 1.0]);
+
+    let Some(DebugValue::Histogram(windows)) =
+        metric(&snapshot, "batched_window_seconds", &[("name", "count")])
+    else {
+        panic!("missing batched_window_seconds");
+    };
+    assert!(windows[0].into_inner() < 0.05);
+    assert!(windows[1].into_inner() >= 0.05);
+
+    let depth = metric(&snapshot, "batched_queue_depth", &[("name", "count")]);
+    assert!(matches!(depth, Some(DebugValue::Gauge(depth)) if depth.into_inner() == 0.0));
+    assert!(metric(&snapshot, "batched_duration_seconds", &[("name", "count")]).is_some());
+}
@@ -0,0 +1,35 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use batched::{Batcher, batched};
+
+// Shuts down every batcher of the process, so it lives in its own test binary
+#[tokio::test]
+async fn shutdown_all() {
+    static PROCESSED: AtomicUsize = AtomicUsize::new(0);
+
+    #[batched(window = 10000, asynchronous)]
+    fn record(numbers: Vec<u32>) {
+        PROCESSED.fetch_add(numbers.len(), Ordering::SeqCst);
+    }
+
+    let batcher = Batcher::builder(async |numbers: Vec<u32>| numbers.len())
+        .window(Duration::from_secs(10))
+        .build();
+    let call = tokio::spawn({
+        let batcher = batcher.clone();
+        async move { batcher.call_many(vec![1, 2]).await }
+    });
+    record_multiple(vec![1, 2, 3]).await;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    batched::shutdown_all().await;
+    assert_eq!(PROCESSED.load(Ordering::SeqCst), 3);
+    assert_eq!(call.await.unwrap(), 2);
+    assert!(batcher.is_shut_down());
+
+    let created_later = Batcher::builder(async |numbers: Vec<u32>| numbers.len()).build();
+    assert!(created_later.is_shut_down());
+}
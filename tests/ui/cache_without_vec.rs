@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100, cache(ttl = "5s"))]
+fn add(numbers: Vec<u32>) -> u32 {
+    numbers.iter().sum()
+}
+
+fn main() {}
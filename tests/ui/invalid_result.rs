@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100)]
+async fn insert(rows: Vec<u32>) -> Result<usize> {
+    Ok(rows.len())
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(limit = 1000)]
+async fn add(numbers: Vec<u32>) -> u32 {
+    numbers.iter().sum()
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100)]
+async fn insert(tenant: u32, table: String, rows: Vec<u32>) -> usize {
+    rows.len()
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100)]
+async fn add(number: u32) -> u32 {
+    number
+}
+
+fn main() {}
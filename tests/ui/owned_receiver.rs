@@ -0,0 +1,13 @@
+use batched::batched;
+
+#[derive(Clone)]
+struct Counter;
+
+impl Counter {
+    #[batched(window = 100)]
+    async fn add(self, numbers: Vec<u32>) -> u32 {
+        numbers.iter().sum()
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100)]
+async fn name(ids: Vec<u32>) -> &'static str {
+    "name"
+}
+
+fn main() {}
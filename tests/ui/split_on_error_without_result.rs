@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100, split_on_error)]
+async fn insert(rows: Vec<u32>) -> Vec<u32> {
+    rows
+}
+
+fn main() {}
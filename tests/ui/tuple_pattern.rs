@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100)]
+async fn add((numbers, _): (Vec<u32>, u32)) -> u32 {
+    numbers.iter().sum()
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use batched::batched;
+
+#[batched(window = 100, window10 = 50, window5 = 10)]
+async fn add(numbers: Vec<u32>) -> u32 {
+    numbers.iter().sum()
+}
+
+fn main() {}
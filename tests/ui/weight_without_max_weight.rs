@@ -0,0 +1,12 @@
+use batched::batched;
+
+fn weight(value: &str) -> u64 {
+    value.len() as u64
+}
+
+#[batched(window = 100, weight = weight)]
+async fn lengths(values: Vec<String>) -> Vec<usize> {
+    values.iter().map(String::len).collect()
+}
+
+fn main() {}